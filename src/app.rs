@@ -1,27 +1,103 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use bevy::asset::{AssetMode, AssetPlugin};
 use bevy::prelude::*;
+use bevy::render::mesh::Mesh;
+use bevy::scene::ScenePlugin;
+use bevy::transform::systems::{propagate_transforms, sync_simple_transforms};
 use bevy::window::{Window, WindowPlugin};
 use bevy_rapier2d::prelude::*;
 
+use crate::bundles::hero::HeroSensors;
+use crate::components::bumper::BumpEvent;
+use crate::components::cliff::CliffEvent;
 use crate::components::collectible::CollectionStats;
+use crate::components::landmark::LandmarkObservation;
+use crate::components::lidar::LaserScan;
+use crate::components::occupancy_grid::{OccupancyGridReset, OccupancyGridUpdated};
 use crate::plugins::auto_nav::auto_nav_plugin::AutoNavPlugin;
+use crate::plugins::auto_nav::follow_path_system::{follow_path_system, hazard_backoff_system};
+use crate::plugins::localization::localization_plugin::LocalizationPlugin;
+use crate::plugins::map_server::map_server_plugin::MapServerPlugin;
+use crate::plugins::sim::sim_constants::SIM_RATE_HZ;
+use crate::plugins::sim::sim_plugin::SimPlugin;
+use crate::plugins::slam::slam_plugin::SlamPlugin;
 use crate::systems::collectibles::{
     collect_on_collision, flood_spawn_collectibles_from_map, CollectibleFloodState,
 };
-use crate::systems::level::{setup_level_loading, spawn_level};
+use crate::systems::collision_cache::CollisionCacheMode;
+use crate::systems::level::{level_ready, setup_level_loading, spawn_level};
+use crate::systems::level_descriptor::{LevelDescriptor, LevelDescriptorLoader, LevelSelection};
+use crate::systems::robot::bumper::bumper_sensor_system;
+use crate::systems::robot::cliff::cliff_sensor_system;
 use crate::systems::robot::cmd_vel_drive::cmd_vel_to_velocity_system;
 use crate::systems::robot::input_keyboard::keyboard_control_system;
+use crate::systems::robot::landmark_detector::landmark_detector_system;
+use crate::systems::robot::laser_scan::assemble_laser_scan_system;
 use crate::systems::robot::lidar_sensor::{lidar_debug_draw_system, lidar_sensor_system};
 use crate::systems::robot::occupancy_grid::{
-    draw_occupancy_grid_system, update_occupancy_grid_system,
+    draw_occupancy_grid_system, mark_hazards_system, publish_occupancy_grid_updates_system,
+    update_occupancy_grid_system,
 };
+use crate::systems::robot::odometry::wheel_odometry_system;
+use crate::systems::robot::transform_tree::update_transform_tree_system;
 use crate::systems::startup::setup;
 use crate::ui::stats_overlay::StatsOverlayPlugin;
 
+/// How the simulation is presented.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SimMode {
+    /// Browser canvas / native window with sprites, HUD and gizmo debug draw.
+    Windowed,
+    /// No window or renderer: physics, sensing, mapping and auto-nav only.
+    Headless,
+}
+
+impl SimMode {
+    pub fn is_windowed(&self) -> bool {
+        *self == SimMode::Windowed
+    }
+}
+
+/// Robot pipeline stages, run in this order every tick.
+///
+/// `Input` runs per frame in `Update`; the rest run per fixed step in
+/// `FixedUpdate`, ahead of Rapier. The explicit chain keeps runs reproducible:
+/// without it, Bevy is free to reorder systems that touch the same components.
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RobotSet {
+    /// Keyboard / mode toggles
+    Input,
+    /// Transform tree refresh, cliff sensors, LiDAR ray casting, scan assembly,
+    /// landmark detection, then localization / EKF SLAM
+    Sense,
+    /// Scan matching and pose graph (SLAM), then occupancy grid integration
+    Map,
+    /// Frontier selection + A*
+    Plan,
+    /// Path following and CmdVel → velocity
+    Act,
+}
+
+/// Per-fixed-step transform propagation (see `build_app_with_mode`)
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SimTransformSet;
+
 pub fn build_app() -> App {
+    build_app_with_mode(SimMode::Windowed)
+}
+
+/// Builds the simulation without a window, renderer or UI (CI / batch runs).
+pub fn build_headless_app() -> App {
+    build_app_with_mode(SimMode::Headless)
+}
+
+pub fn build_app_with_mode(mode: SimMode) -> App {
     let mut app = App::new();
 
-    // Asset and window plugins
+    app.insert_resource(mode);
+
+    // Asset plugin (shared), then either the full window/render stack or a minimal one
     let asset_plugin = AssetPlugin {
         file_path: "assets".into(),
         mode: AssetMode::Unprocessed,
@@ -29,33 +105,61 @@ pub fn build_app() -> App {
         ..default()
     };
 
-    let window_plugin = WindowPlugin {
-        primary_window: Some(Window {
-            canvas: Some("#bevy-canvas".into()),
-            ..default()
-        }),
-        ..default()
-    };
-
-    let plugins = DefaultPlugins
-        .set(ImagePlugin::default_nearest())
-        .set(asset_plugin)
-        .set(window_plugin);
-
-    app.add_plugins(plugins);
-
-    app.add_plugins(StatsOverlayPlugin);
+    match mode {
+        SimMode::Windowed => add_windowed_plugins(&mut app, asset_plugin),
+        SimMode::Headless => add_headless_plugins(&mut app, asset_plugin),
+    }
 
     // Physics
     app.insert_resource(RapierConfiguration {
         gravity: Vec2::ZERO,
+        timestep_mode: TimestepMode::Fixed {
+            dt: (1.0 / SIM_RATE_HZ) as f32,
+            substeps: 1,
+        },
         ..default()
     });
-    app.add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0));
+    app.add_plugins(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0).in_fixed_schedule());
+
+    // Bevy only propagates transforms in PostUpdate (once per frame). When several
+    // fixed steps run in one frame, the robot must still see where Rapier moved it.
+    app.add_systems(
+        FixedUpdate,
+        (sync_simple_transforms, propagate_transforms)
+            .in_set(SimTransformSet)
+            .after(PhysicsSet::Writeback),
+    );
+
+    // Seeding, fixed-step rate / time scale, deterministic stepping
+    app.add_plugins(SimPlugin);
+
+    // Robot pipeline order. Held until the level is fully spawned (asset load time
+    // varies run to run). `FixedUpdate` runs before `Update`, so the first scan
+    // always sees colliders Rapier has already registered.
+    app.configure_sets(Update, RobotSet::Input.run_if(level_ready));
+    app.configure_sets(
+        FixedUpdate,
+        (
+            RobotSet::Sense,
+            RobotSet::Map,
+            RobotSet::Plan,
+            RobotSet::Act,
+        )
+            .chain()
+            .run_if(level_ready)
+            .before(PhysicsSet::SyncBackend),
+    );
 
     // Game setup systems (run once at startup)
+    app.init_resource::<HeroSensors>();
     app.add_systems(Startup, setup);
 
+    // Level descriptors (`assets/levels/*.level.ron`)
+    app.init_asset::<LevelDescriptor>()
+        .init_asset_loader::<LevelDescriptorLoader>()
+        .init_resource::<LevelSelection>()
+        .init_resource::<CollisionCacheMode>();
+
     // In Startup:
     app.add_systems(Startup, setup_level_loading);
 
@@ -64,33 +168,122 @@ pub fn build_app() -> App {
     app.add_systems(Update, flood_spawn_collectibles_from_map.after(spawn_level));
 
     // Player input + movement
-    app.add_systems(Update, keyboard_control_system);
-    app.add_systems(Update, cmd_vel_to_velocity_system);
+    if mode.is_windowed() {
+        app.add_systems(Update, keyboard_control_system.in_set(RobotSet::Input));
+    }
+    app.add_systems(
+        FixedUpdate,
+        cmd_vel_to_velocity_system
+            .in_set(RobotSet::Act)
+            .after(follow_path_system)
+            .after(hazard_backoff_system),
+    );
 
     app.add_plugins(AutoNavPlugin);
 
-    // Sensors
+    // Sensors (one `LaserScan` event per LiDAR revolution; landmark sightings)
+    app.add_event::<LaserScan>()
+        .add_event::<LandmarkObservation>();
     app.add_systems(
-        Update,
+        FixedUpdate,
         (
+            wheel_odometry_system,
+            update_transform_tree_system,
+            cliff_sensor_system,
             lidar_sensor_system,
-            lidar_debug_draw_system.after(lidar_sensor_system),
-        ),
+            assemble_laser_scan_system,
+            landmark_detector_system,
+        )
+            .chain()
+            .in_set(RobotSet::Sense),
+    );
+    // Localization against a known map (heroes fitted with a localizer only)
+    app.add_plugins(LocalizationPlugin);
+
+    // Bumper contacts come from Rapier events, read straight after the step
+    app.add_event::<BumpEvent>().add_event::<CliffEvent>();
+    app.add_systems(
+        FixedUpdate,
+        bumper_sensor_system
+            .after(SimTransformSet)
+            .run_if(level_ready),
     );
 
-    // Occupancy grid
+    // Occupancy grid (LiDAR, then bumper / cliff hazards, then what changed)
+    app.add_event::<OccupancyGridReset>()
+        .add_event::<OccupancyGridUpdated>();
     app.add_systems(
-        Update,
+        FixedUpdate,
         (
             update_occupancy_grid_system,
-            draw_occupancy_grid_system.after(update_occupancy_grid_system),
-        ),
+            mark_hazards_system,
+            publish_occupancy_grid_updates_system,
+        )
+            .chain()
+            .in_set(RobotSet::Map),
     );
+    // SLAM: scan matching corrects the pose before the grid update (heroes fitted with it only)
+    app.add_plugins(SlamPlugin);
+    // Map export / import in ROS map_server format (O saves, L loads)
+    app.add_plugins(MapServerPlugin);
+
+    // Debug draw (gizmos need the render stack)
+    if mode.is_windowed() {
+        app.add_systems(
+            Update,
+            (lidar_debug_draw_system, draw_occupancy_grid_system),
+        );
+    }
 
     // Collectibles: counter + collision detection
     app.insert_resource(CollectionStats::default());
-    app.add_systems(Update, collect_on_collision);
+    // (Rapier events are read per fixed step, straight after the step that produced them)
+    app.add_systems(
+        FixedUpdate,
+        collect_on_collision.after(PhysicsSet::Writeback),
+    );
     app.insert_resource(CollectibleFloodState::default());
 
     app
 }
+
+fn add_windowed_plugins(app: &mut App, asset_plugin: AssetPlugin) {
+    let window_plugin = WindowPlugin {
+        primary_window: Some(Window {
+            canvas: Some("#bevy-canvas".into()),
+            ..default()
+        }),
+        ..default()
+    };
+
+    let plugins = DefaultPlugins
+        .set(ImagePlugin::default_nearest())
+        .set(asset_plugin)
+        .set(window_plugin);
+
+    app.add_plugins(plugins);
+
+    app.add_plugins(StatsOverlayPlugin);
+}
+
+fn add_headless_plugins(app: &mut App, asset_plugin: AssetPlugin) {
+    // Only one global logger per process (the batch runner builds an app per episode)
+    static LOG_INSTALLED: AtomicBool = AtomicBool::new(false);
+    if !LOG_INSTALLED.swap(true, Ordering::SeqCst) {
+        app.add_plugins(bevy::log::LogPlugin::default());
+    }
+
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        HierarchyPlugin,
+        // Auto-nav still listens for its toggle keys; nothing will press them.
+        bevy::input::InputPlugin,
+        asset_plugin,
+        // Level masks are read from `Image` assets, so we need the loader but no GPU.
+        ImagePlugin::default_nearest(),
+        // Rapier's async-collider systems expect mesh and scene storage to exist.
+        ScenePlugin,
+    ));
+    app.init_asset::<Mesh>();
+}
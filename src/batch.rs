@@ -0,0 +1,396 @@
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use bevy::app::PluginsState;
+use bevy::prelude::*;
+
+use crate::bundles::hero::HeroSensors;
+use crate::components::collectible::CollectionStats;
+use crate::constants::METERS_PER_PIXEL;
+use crate::plugins::localization::likelihood_field::KnownMap;
+use crate::plugins::map_server::map_file::load_map;
+use crate::plugins::sim::coverage::CoverageStats;
+use crate::plugins::sim::kidnap::KidnapRequest;
+use crate::plugins::sim::sim_metrics::SimMetrics;
+use crate::plugins::sim::sim_plugin::make_deterministic;
+use crate::plugins::sim::sim_time::{SimHaltAt, SimSpeed, SimTiming};
+use crate::systems::collision_cache::CollisionCacheMode;
+use crate::systems::level::LevelAssets;
+use crate::systems::level_descriptor::LevelSelection;
+
+/// Give up on an episode whose level never finishes loading (wall-clock)
+const LEVEL_LOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// One headless episode of a level
+#[derive(Clone, Debug)]
+pub struct EpisodeConfig {
+    pub seed: u64,
+    /// Simulated seconds before the episode is cut off
+    pub time_limit_secs: f32,
+    pub timing: SimTiming,
+    /// Level descriptor path relative to `assets/` (default level if `None`)
+    pub level: Option<String>,
+    pub sensors: HeroSensors,
+    /// Teleport the hero to a random spot at this sim time (kidnapped-robot test)
+    pub kidnap_at_secs: Option<f32>,
+    /// `map_server` YAML of a map to start from (also localized against)
+    pub map: Option<String>,
+}
+
+impl Default for EpisodeConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            time_limit_secs: 600.0,
+            timing: SimTiming {
+                speed: SimSpeed::Max,
+                ..default()
+            },
+            level: None,
+            sensors: HeroSensors::default(),
+            kidnap_at_secs: None,
+            map: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct EpisodeReport {
+    pub seed: u64,
+    pub collected: usize,
+    pub total: usize,
+    /// Share of the reachable free space the hero has mapped (0–100)
+    pub explored_pct: f32,
+    pub distance_m: f32,
+    pub wall_contacts: usize,
+    pub replans: usize,
+    pub path_removals: usize,
+    pub grid_resets: usize,
+    pub rotating_secs: f32,
+    pub stuck_secs: f32,
+    /// Planned ÷ driven length over completed paths (None = no path completed)
+    pub path_efficiency: Option<f32>,
+    /// Wheel-odometry position error at the end of the episode (metres)
+    pub odometry_error_m: f32,
+    /// Worst wheel-odometry position error during the episode (metres)
+    pub odometry_error_max_m: f32,
+    /// Localizer position error at the end of the episode (metres; None = no localizer)
+    pub localization_error_m: Option<f32>,
+    /// Seconds the localizer took to find the hero again after the kidnap
+    /// (None = no kidnap, or never recovered)
+    pub kidnap_recovery_secs: Option<f32>,
+    /// RMS error of the pose the hero mapped with, over every step (metres)
+    pub pose_error_rmse_m: f32,
+    /// Worst error of the pose the hero mapped with (metres)
+    pub pose_error_max_m: f32,
+    /// Loop closures found by the pose-graph back end
+    pub loop_closures: usize,
+    /// Times the occupancy grid was re-rendered after a loop closure
+    pub map_rebuilds: usize,
+    /// Mean EKF landmark position error at the end of the episode (metres;
+    /// None = no EKF SLAM)
+    pub landmark_error_m: Option<f32>,
+    /// Simulated seconds the episode ran for
+    pub sim_secs: f32,
+    /// Simulated seconds until every collectible was picked up (None = timed out)
+    pub completed_at_secs: Option<f32>,
+    /// Real seconds the episode took to simulate
+    pub wall_secs: f32,
+}
+
+/// Runs one deterministic headless episode until everything is collected or
+/// the time limit is reached (on the last step not past it, at any speed).
+pub fn run_episode(config: &EpisodeConfig) -> EpisodeReport {
+    let started = Instant::now();
+
+    let mut app = crate::app::build_headless_app();
+    app.insert_resource(config.timing);
+    app.insert_resource(config.sensors.clone());
+    if let Some(level) = &config.level {
+        app.insert_resource(LevelSelection(level.clone()));
+    }
+    if let Some(map) = config.map.as_deref().and_then(load_map) {
+        app.insert_resource(KnownMap(map));
+    }
+    make_deterministic(&mut app, config.seed);
+    finish_plugins(&mut app);
+
+    // Fixed stepping halts on the exact step of the kidnap, then of the time
+    // limit, however many steps `SimSpeed::Max` packs into a frame
+    let mut kidnap_pending = config
+        .kidnap_at_secs
+        .filter(|&at| at < config.time_limit_secs);
+    app.insert_resource(SimHaltAt(kidnap_pending.unwrap_or(config.time_limit_secs)));
+    loop {
+        app.update();
+
+        let metrics = app.world.resource::<SimMetrics>();
+        if metrics.completed_at_secs.is_some() {
+            break;
+        }
+        let timing = app.world.resource::<SimTiming>();
+        if app.world.resource::<SimHaltAt>().reached(metrics, timing) {
+            if kidnap_pending.take().is_none() {
+                break;
+            }
+            app.world.send_event(KidnapRequest);
+            app.insert_resource(SimHaltAt(config.time_limit_secs));
+            continue;
+        }
+        if metrics.elapsed_secs == 0.0 && started.elapsed() > LEVEL_LOAD_TIMEOUT {
+            warn!(
+                "[Batch] Level did not load for seed {} — skipping",
+                config.seed
+            );
+            break;
+        }
+    }
+
+    let (collected, total) = {
+        let stats = app.world.resource::<CollectionStats>();
+        (stats.collected, stats.total)
+    };
+    let metrics = app.world.resource::<SimMetrics>().clone();
+    let explored_pct = app.world.resource::<CoverageStats>().explored_pct;
+
+    EpisodeReport {
+        seed: config.seed,
+        collected,
+        total,
+        explored_pct,
+        distance_m: metrics.distance_travelled * METERS_PER_PIXEL,
+        wall_contacts: metrics.wall_contacts,
+        replans: metrics.replans,
+        path_removals: metrics.path_removals(),
+        grid_resets: metrics.grid_resets,
+        rotating_secs: metrics.rotating_in_place_secs,
+        stuck_secs: metrics.stuck_secs,
+        path_efficiency: metrics.path_efficiency(),
+        odometry_error_m: metrics.odometry_error_px * METERS_PER_PIXEL,
+        odometry_error_max_m: metrics.odometry_error_max_px * METERS_PER_PIXEL,
+        localization_error_m: metrics
+            .localization_error_px
+            .map(|error| error * METERS_PER_PIXEL),
+        kidnap_recovery_secs: metrics.kidnap_recovery_secs,
+        pose_error_rmse_m: metrics.pose_error_rmse_px() * METERS_PER_PIXEL,
+        pose_error_max_m: metrics.pose_error_max_px * METERS_PER_PIXEL,
+        loop_closures: metrics.loop_closures,
+        map_rebuilds: metrics.map_rebuilds,
+        landmark_error_m: metrics
+            .landmark_error_px
+            .map(|error| error * METERS_PER_PIXEL),
+        sim_secs: metrics.elapsed_secs,
+        completed_at_secs: metrics.completed_at_secs,
+        wall_secs: started.elapsed().as_secs_f32(),
+    }
+}
+
+/// What `App::run` would do before handing over to the runner
+fn finish_plugins(app: &mut App) {
+    while app.plugins_state() == PluginsState::Adding {
+        bevy::tasks::tick_global_task_pools_on_main_thread();
+    }
+    app.finish();
+    app.cleanup();
+}
+
+/// Every `*.level.ron` under `assets/levels/`, as asset paths
+pub fn level_paths() -> Vec<String> {
+    let mut paths: Vec<String> = std::fs::read_dir("assets/levels")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| name.ends_with(".level.ron"))
+        .map(|name| format!("levels/{name}"))
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Regenerates the collision cache of every level (headless, one app per level).
+/// Returns the levels that were rebuilt.
+pub fn rebuild_collision_caches() -> Vec<String> {
+    let mut rebuilt = Vec::new();
+
+    for level in level_paths() {
+        let started = Instant::now();
+        let mut app = crate::app::build_headless_app();
+        app.insert_resource(LevelSelection(level.clone()))
+            .insert_resource(CollisionCacheMode::Rebuild);
+        finish_plugins(&mut app);
+
+        loop {
+            app.update();
+            if app.world.resource::<LevelAssets>().spawned {
+                rebuilt.push(level);
+                break;
+            }
+            if started.elapsed() > LEVEL_LOAD_TIMEOUT {
+                warn!("[Cache] Level {level} did not load — skipped");
+                break;
+            }
+        }
+    }
+
+    rebuilt
+}
+
+pub fn reports_to_json(reports: &[EpisodeReport]) -> String {
+    let mut out = String::from("{\n  \"episodes\": [\n");
+    for (i, r) in reports.iter().enumerate() {
+        let completed = r
+            .completed_at_secs
+            .map_or("null".to_string(), |t| format!("{t:.3}"));
+        let efficiency = r
+            .path_efficiency
+            .map_or("null".to_string(), |e| format!("{e:.4}"));
+        let localization = r
+            .localization_error_m
+            .map_or("null".to_string(), |e| format!("{e:.3}"));
+        let recovery = r
+            .kidnap_recovery_secs
+            .map_or("null".to_string(), |t| format!("{t:.3}"));
+        let landmark = r
+            .landmark_error_m
+            .map_or("null".to_string(), |e| format!("{e:.3}"));
+        let _ = write!(
+            out,
+            "    {{\"seed\": {}, \"collected\": {}, \"total\": {}, \"explored_pct\": {:.2}, \
+             \"distance_m\": {:.3}, \"wall_contacts\": {}, \"replans\": {}, \
+             \"path_removals\": {}, \"grid_resets\": {}, \"rotating_secs\": {:.3}, \
+             \"stuck_secs\": {:.3}, \"path_efficiency\": {}, \"odometry_error_m\": {:.3}, \
+             \"odometry_error_max_m\": {:.3}, \"localization_error_m\": {}, \
+             \"kidnap_recovery_secs\": {}, \"pose_error_rmse_m\": {:.3}, \"pose_error_max_m\": {:.3}, \
+             \"loop_closures\": {}, \"map_rebuilds\": {}, \
+             \"landmark_error_m\": {}, \"sim_secs\": {:.3}, \"completed_at_secs\": {}, \"wall_secs\": {:.3}}}",
+            r.seed,
+            r.collected,
+            r.total,
+            r.explored_pct,
+            r.distance_m,
+            r.wall_contacts,
+            r.replans,
+            r.path_removals,
+            r.grid_resets,
+            r.rotating_secs,
+            r.stuck_secs,
+            efficiency,
+            r.odometry_error_m,
+            r.odometry_error_max_m,
+            localization,
+            recovery,
+            r.pose_error_rmse_m,
+            r.pose_error_max_m,
+            r.loop_closures,
+            r.map_rebuilds,
+            landmark,
+            r.sim_secs,
+            completed,
+            r.wall_secs,
+        );
+        out.push_str(if i + 1 < reports.len() { ",\n" } else { "\n" });
+    }
+
+    let n = reports.len().max(1) as f32;
+    let completed = reports
+        .iter()
+        .filter(|r| r.completed_at_secs.is_some())
+        .count();
+    let collected_frac = reports
+        .iter()
+        .map(|r| r.collected as f32 / r.total.max(1) as f32)
+        .sum::<f32>()
+        / n;
+    let _ = write!(
+        out,
+        "  ],\n  \"summary\": {{\"episodes\": {}, \"completed\": {}, \"mean_collected_fraction\": {:.4}}}\n}}\n",
+        reports.len(),
+        completed,
+        collected_frac,
+    );
+    out
+}
+
+pub fn reports_to_csv(reports: &[EpisodeReport]) -> String {
+    let mut out = String::from(
+        "seed,collected,total,explored_pct,distance_m,wall_contacts,replans,path_removals,grid_resets,\
+         rotating_secs,stuck_secs,path_efficiency,odometry_error_m,odometry_error_max_m,localization_error_m,\
+         kidnap_recovery_secs,pose_error_rmse_m,pose_error_max_m,loop_closures,map_rebuilds,landmark_error_m,sim_secs,completed_at_secs,wall_secs\n",
+    );
+    for r in reports {
+        let completed = r
+            .completed_at_secs
+            .map_or(String::new(), |t| format!("{t:.3}"));
+        let efficiency = r
+            .path_efficiency
+            .map_or(String::new(), |e| format!("{e:.4}"));
+        let localization = r
+            .localization_error_m
+            .map_or(String::new(), |e| format!("{e:.3}"));
+        let recovery = r
+            .kidnap_recovery_secs
+            .map_or(String::new(), |t| format!("{t:.3}"));
+        let landmark = r
+            .landmark_error_m
+            .map_or(String::new(), |e| format!("{e:.3}"));
+        let _ = writeln!(
+            out,
+            "{},{},{},{:.2},{:.3},{},{},{},{},{:.3},{:.3},{},{:.3},{:.3},{},{},{:.3},{:.3},{},{},{},{:.3},{},{:.3}",
+            r.seed,
+            r.collected,
+            r.total,
+            r.explored_pct,
+            r.distance_m,
+            r.wall_contacts,
+            r.replans,
+            r.path_removals,
+            r.grid_resets,
+            r.rotating_secs,
+            r.stuck_secs,
+            efficiency,
+            r.odometry_error_m,
+            r.odometry_error_max_m,
+            localization,
+            recovery,
+            r.pose_error_rmse_m,
+            r.pose_error_max_m,
+            r.loop_closures,
+            r.map_rebuilds,
+            landmark,
+            r.sim_secs,
+            completed,
+            r.wall_secs,
+        );
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The report with its wall-clock field, the one thing allowed to differ
+    fn sim_report(config: &EpisodeConfig) -> String {
+        let report = run_episode(config);
+        assert!(report.sim_secs <= config.time_limit_secs);
+        format!(
+            "{:?}",
+            EpisodeReport {
+                wall_secs: 0.0,
+                ..report
+            }
+        )
+    }
+
+    #[test]
+    fn same_seed_episodes_match_at_max_speed() {
+        let config = EpisodeConfig {
+            seed: 5,
+            time_limit_secs: 8.0,
+            kidnap_at_secs: Some(3.0),
+            ..default()
+        };
+        assert_eq!(sim_report(&config), sim_report(&config));
+    }
+}
@@ -0,0 +1,98 @@
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    // Native-only tool.
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use pick_e::batch::{reports_to_csv, reports_to_json, run_episode, EpisodeConfig};
+
+    // Usage: pick-e-batch [--episodes <n>] [--seed <u64>] [--time-limit <secs>]
+    //                     [--rate <hz>] [--speed <1|10|max>] [--out <report.json|report.csv>]
+    //                     [--level <levels/name.level.ron>] [--lidar-noise] [--odometry] [--amcl]
+    //                     [--kidnap-at <secs>] [--slam] [--pose-graph] [--ekf-slam]
+    //                     [--map <maps/name.yaml>]
+    let args: Vec<String> = std::env::args().collect();
+    let value_of = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    let episodes: u64 = value_of("--episodes")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(5);
+    let base_seed: u64 = value_of("--seed").and_then(|s| s.parse().ok()).unwrap_or(0);
+    let out_path = value_of("--out")
+        .cloned()
+        .unwrap_or_else(|| "batch-report.json".to_string());
+
+    let mut config = EpisodeConfig {
+        level: value_of("--level").cloned(),
+        // Start from a saved map_server map (e.g. one saved with O)
+        map: value_of("--map").cloned(),
+        ..Default::default()
+    };
+    if args.iter().any(|arg| arg == "--lidar-noise") {
+        config.sensors.lidar_noise = pick_e::LidarNoise::typical();
+    }
+    // Map and navigate on drifting wheel odometry instead of the true pose
+    if args.iter().any(|arg| arg == "--odometry") {
+        config.sensors.odometry = pick_e::WheelOdometry::typical();
+        config.sensors.pose_source = pick_e::PoseSource::Odometry;
+    }
+    // Localize against the level map with a particle filter
+    if args.iter().any(|arg| arg == "--amcl") {
+        config.sensors.localization = pick_e::Localization::Amcl;
+    }
+    // Correct odometry by matching each scan against the map being built
+    if args.iter().any(|arg| arg == "--slam") {
+        config.sensors.slam = pick_e::Slam::ScanMatching;
+    }
+    // ...plus a pose graph that closes loops and re-renders the map
+    if args.iter().any(|arg| arg == "--pose-graph") {
+        config.sensors.slam = pick_e::Slam::PoseGraph;
+    }
+    // Landmark EKF SLAM over the collectibles instead
+    if args.iter().any(|arg| arg == "--ekf-slam") {
+        config.sensors.slam = pick_e::Slam::Ekf;
+    }
+    config.kidnap_at_secs = value_of("--kidnap-at").and_then(|s| s.parse().ok());
+    if let Some(limit) = value_of("--time-limit").and_then(|s| s.parse().ok()) {
+        config.time_limit_secs = limit;
+    }
+    if let Some(rate_hz) = value_of("--rate").and_then(|s| s.parse().ok()) {
+        config.timing.rate_hz = rate_hz;
+    }
+    if let Some(speed) = value_of("--speed").and_then(|s| pick_e::SimSpeed::parse(s)) {
+        config.timing.speed = speed;
+    }
+
+    let mut reports = Vec::new();
+    for i in 0..episodes {
+        config.seed = base_seed + i;
+        let report = run_episode(&config);
+        println!(
+            "episode {}/{} seed={} collected={}/{} sim={:.1}s wall={:.1}s",
+            i + 1,
+            episodes,
+            report.seed,
+            report.collected,
+            report.total,
+            report.sim_secs,
+            report.wall_secs,
+        );
+        reports.push(report);
+    }
+
+    let text = if out_path.ends_with(".csv") {
+        reports_to_csv(&reports)
+    } else {
+        reports_to_json(&reports)
+    };
+    if let Err(e) = std::fs::write(&out_path, text) {
+        eprintln!("Failed to write {out_path}: {e}");
+        std::process::exit(1);
+    }
+    println!("Wrote {out_path}");
+}
@@ -1,46 +1,65 @@
 use bevy::prelude::*;
-use bevy::sprite::SpriteBundle;
 use bevy_rapier2d::prelude::*;
 
+use crate::components::bumper::BumperSensor;
+use crate::components::cliff::CliffSensors;
 use crate::components::cmd_vel::CmdVel;
+use crate::components::frames::TransformTree;
 use crate::components::lidar::*;
 use crate::components::occupancy_grid::OccupancyGrid;
+use crate::components::odometry::{Odometry, PoseSource, WheelOdometry};
 use crate::constants::*;
+use crate::plugins::localization::localization_plugin::Localization;
+use crate::plugins::slam::slam_plugin::Slam;
 
 /// Marker for input control
 #[derive(Component)]
 pub struct HeroController;
 
+/// Sensor models fitted to the hero when it is spawned (set before the app runs)
+#[derive(Resource, Clone, Debug, Default)]
+pub struct HeroSensors {
+    pub lidar: LidarConfig,
+    pub lidar_noise: LidarNoise,
+    pub odometry: WheelOdometry,
+    /// Pose used for mapping and navigation
+    pub pose_source: PoseSource,
+    pub localization: Localization,
+    pub slam: Slam,
+}
+
 pub const HERO_RADIUS: f32 = HERO_RADIUS_PX;
 pub const HERO_SIZE: Vec2 = Vec2::new(HERO_RADIUS * 2.0, HERO_RADIUS * 2.0);
 
-pub fn hero_bundle(asset_server: &AssetServer) -> impl Bundle {
+/// The hero starts at the origin; `spawn_level` moves it to the level's spawn pose.
+pub fn hero_bundle(sensors: &HeroSensors) -> impl Bundle {
     (
-        sprite_bundle(asset_server),
+        SpatialBundle::default(),
         physics_bundle(),
-        perception_bundle(),
+        perception_bundle(sensors),
         HeroController,
         CmdVel::default(),
         Name::new("Hero"),
     )
 }
 
-fn sprite_bundle(asset_server: &AssetServer) -> SpriteBundle {
-    SpriteBundle {
-        texture: asset_server.load("textures/hero.png"),
-        sprite: Sprite {
+/// Visuals only — inserted on top of `hero_bundle` when running windowed
+pub fn hero_sprite_bundle(asset_server: &AssetServer) -> impl Bundle {
+    (
+        Sprite {
             custom_size: Some(HERO_SIZE),
             ..default()
         },
-        transform: Transform::from_xyz(0.0, 200.0, 0.0),
-        ..default()
-    }
+        asset_server.load::<Image>("textures/hero.png"),
+    )
 }
 
 fn physics_bundle() -> impl Bundle {
     (
         RigidBody::Dynamic,
         Collider::ball(HERO_RADIUS),
+        // Wall contacts are reported for `SimMetrics`
+        ActiveEvents::COLLISION_EVENTS,
         Velocity::default(),
         Damping {
             linear_damping: 2.0,
@@ -49,15 +68,19 @@ fn physics_bundle() -> impl Bundle {
     )
 }
 
-pub fn perception_bundle() -> impl Bundle {
+pub fn perception_bundle(sensors: &HeroSensors) -> impl Bundle {
     (
+        BumperSensor::default(),
+        CliffSensors::default(),
         LidarSensor,
         LidarEmitter::default(),
-        OccupancyGrid::new(
-            LOGICAL_W as usize,
-            LOGICAL_H as usize,
-            OCCUPANCY_GRID_RES,
-            Vec2::new(-LOGICAL_H, -LOGICAL_W),
-        ),
+        LaserScanAccumulator::default(),
+        sensors.lidar,
+        sensors.lidar_noise,
+        sensors.odometry,
+        Odometry::default(),
+        sensors.pose_source,
+        TransformTree::default(),
+        OccupancyGrid::new(OCCUPANCY_GRID_RES, Vec2::ZERO),
     )
 }
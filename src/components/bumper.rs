@@ -0,0 +1,21 @@
+use bevy::prelude::*;
+
+/// Front bumper split into two switches. The hero's ball collider doubles as
+/// the bumper shell: contacts on its front half press the side they land on.
+#[derive(Component, Debug, Clone, Default)]
+pub struct BumperSensor {
+    pub left_pressed: bool,
+    pub right_pressed: bool,
+    /// Colliders currently touching the hero (from Rapier collision events)
+    pub touching: Vec<Entity>,
+}
+
+/// A bumper switch closed this step
+#[derive(Event, Debug, Clone, Copy)]
+pub struct BumpEvent {
+    pub entity: Entity,
+    pub left: bool,
+    pub right: bool,
+    /// Contact point in the robot's base frame
+    pub local_point: Vec2,
+}
@@ -0,0 +1,35 @@
+use bevy::prelude::*;
+
+use crate::constants::HERO_RADIUS_PX;
+
+/// Downward-facing IR sensors near the front rim that see a drop (stairs,
+/// ledges) before the wheels reach it
+#[derive(Component, Debug, Clone)]
+pub struct CliffSensors {
+    /// Sensor positions in the robot's base frame (x forward, y left)
+    pub offsets: Vec<Vec2>,
+    /// Parallel to `offsets`
+    pub triggered: Vec<bool>,
+}
+
+impl Default for CliffSensors {
+    /// Front-left and front-right, just inside the shell
+    fn default() -> Self {
+        let offsets: Vec<Vec2> = [35.0_f32, -35.0]
+            .iter()
+            .map(|deg| Vec2::from_angle(deg.to_radians()) * HERO_RADIUS_PX * 0.8)
+            .collect();
+        Self {
+            triggered: vec![false; offsets.len()],
+            offsets,
+        }
+    }
+}
+
+/// A cliff sensor started seeing a drop this step
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CliffEvent {
+    pub entity: Entity,
+    /// The sensor's position in the robot's base frame
+    pub local_point: Vec2,
+}
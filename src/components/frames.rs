@@ -0,0 +1,97 @@
+use std::ops::Mul;
+
+use bevy::prelude::*;
+
+/// A rigid 2D transform: where a child frame sits in its parent frame.
+///
+/// `a_to_b` maps points expressed in frame `b` into frame `a`, so transforms
+/// chain like `map_to_odom * odom_to_base`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose2d {
+    pub translation: Vec2,
+    /// Heading in radians, counter-clockwise from +X
+    pub yaw: f32,
+}
+
+impl Default for Pose2d {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Pose2d {
+    pub const IDENTITY: Self = Self {
+        translation: Vec2::ZERO,
+        yaw: 0.0,
+    };
+
+    pub fn new(translation: Vec2, yaw: f32) -> Self {
+        Self { translation, yaw }
+    }
+
+    /// The planar part of an entity's world transform
+    pub fn from_global(transform: &GlobalTransform) -> Self {
+        let forward = transform.right().truncate();
+        Self::new(
+            transform.translation().truncate(),
+            forward.y.atan2(forward.x),
+        )
+    }
+
+    pub fn yaw_deg(&self) -> f32 {
+        self.yaw.to_degrees()
+    }
+
+    pub fn transform_point(&self, point: Vec2) -> Vec2 {
+        self.translation + Vec2::from_angle(self.yaw).rotate(point)
+    }
+
+    pub fn inverse(&self) -> Self {
+        let yaw = -self.yaw;
+        Self::new(-Vec2::from_angle(yaw).rotate(self.translation), yaw)
+    }
+}
+
+/// `angle` folded into (-π, π]
+pub fn wrap_angle(angle: f32) -> f32 {
+    angle.sin().atan2(angle.cos())
+}
+
+impl Mul for Pose2d {
+    type Output = Pose2d;
+
+    // Composition: headings add
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn mul(self, child: Pose2d) -> Pose2d {
+        Pose2d::new(
+            self.transform_point(child.translation),
+            self.yaw + child.yaw,
+        )
+    }
+}
+
+/// The robot's frame chain, as on the real robot: map → odom → base_link → laser.
+///
+/// - `map_to_odom`: drift correction from localisation (identity until
+///   something estimates it)
+/// - `odom_to_base`: the robot's pose in its odometry frame
+/// - `base_to_laser`: the static sensor mount (`LidarConfig`)
+///
+/// Perception consumes sensor-frame data through this tree rather than the
+/// ground-truth `GlobalTransform`.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct TransformTree {
+    pub map_to_odom: Pose2d,
+    pub odom_to_base: Pose2d,
+    pub base_to_laser: Pose2d,
+}
+
+impl TransformTree {
+    pub fn map_to_base(&self) -> Pose2d {
+        self.map_to_odom * self.odom_to_base
+    }
+
+    pub fn map_to_laser(&self) -> Pose2d {
+        self.map_to_base() * self.base_to_laser
+    }
+}
@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+
+/// Simulated point-feature detector (think a camera spotting fiducials):
+/// reports the range and bearing of each collectible in view, with its
+/// identity, so data association is known.
+///
+/// Draws come from the seeded "landmark_noise" stream.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct LandmarkDetector {
+    /// Detections per second
+    pub rate_hz: f32,
+    /// Furthest detection (px)
+    pub max_range: f32,
+    /// Total field of view, centred on the robot's heading (deg; 360 = all round)
+    pub fov_deg: f32,
+    /// Std-dev of the range (px) and bearing (deg) noise
+    pub range_sigma_px: f32,
+    pub bearing_sigma_deg: f32,
+    /// Chance that a landmark in view is reported
+    pub detection_prob: f32,
+    /// Seconds since the last detection
+    pub since_last: f32,
+}
+
+impl Default for LandmarkDetector {
+    /// A forward-facing camera with a wide lens
+    fn default() -> Self {
+        Self {
+            rate_hz: 5.0,
+            max_range: 250.0,
+            fov_deg: 120.0,
+            range_sigma_px: 3.0,
+            bearing_sigma_deg: 1.0,
+            detection_prob: 0.9,
+            since_last: 0.0,
+        }
+    }
+}
+
+impl LandmarkDetector {
+    /// Whether `bearing` (rad, base frame) is inside the field of view
+    pub fn in_fov(&self, bearing: f32) -> bool {
+        self.fov_deg >= 360.0 || bearing.to_degrees().abs() <= self.fov_deg * 0.5
+    }
+}
+
+/// One landmark seen by a `LandmarkDetector`, in the robot's base frame
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LandmarkObservation {
+    pub entity: Entity,
+    /// The collectible seen
+    pub landmark: Entity,
+    /// Distance (px)
+    pub range: f32,
+    /// Angle from the robot's heading (rad, counter-clockwise)
+    pub bearing: f32,
+}
@@ -1,25 +1,83 @@
 use bevy::prelude::*;
 
+use crate::components::frames::Pose2d;
+use crate::constants::{
+    LIDAR_ANGLE_STEP, LIDAR_FOV_DEG, LIDAR_MAX_RANGE_PX, LIDAR_MIN_RANGE_PX, LIDAR_SPIN_RATE_HZ,
+};
+
 /// Marker for entities with a LIDAR sensor attached
 #[derive(Component)]
 pub struct LidarSensor;
 
+/// Sensor product and mounting: what one `LidarSensor` can see, and from where.
+/// Defaults to the budget scanner described in `constants.rs`.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct LidarConfig {
+    pub spin_rate_hz: f32,
+    /// Angular resolution (degrees between beams)
+    pub angle_step_deg: f32,
+    /// Blind zone (world pixels): closer returns are discarded
+    pub min_range: f32,
+    pub max_range: f32,
+    /// Arc that produces readings, centred on the sensor's forward axis
+    pub fov_deg: f32,
+    /// Sensor position in the robot frame (x forward, y left; world pixels)
+    pub mount_offset: Vec2,
+    /// Sensor forward axis relative to the robot's (degrees, counter-clockwise)
+    pub mount_yaw_deg: f32,
+    /// Publish `LaserScan`s as measured, skewed by the robot's motion during the
+    /// sweep. Off: each scan is re-expressed in the laser frame at its first beam.
+    pub motion_distortion: bool,
+}
+
+impl Default for LidarConfig {
+    fn default() -> Self {
+        Self {
+            spin_rate_hz: LIDAR_SPIN_RATE_HZ,
+            angle_step_deg: LIDAR_ANGLE_STEP,
+            min_range: LIDAR_MIN_RANGE_PX,
+            max_range: LIDAR_MAX_RANGE_PX,
+            fov_deg: LIDAR_FOV_DEG,
+            mount_offset: Vec2::ZERO,
+            mount_yaw_deg: 0.0,
+            motion_distortion: false,
+        }
+    }
+}
+
+impl LidarConfig {
+    /// The laser frame in the robot's base frame
+    pub fn base_to_laser(&self) -> Pose2d {
+        Pose2d::new(self.mount_offset, self.mount_yaw_deg.to_radians())
+    }
+
+    /// Whether a beam at `angle_deg` (sensor frame) lies inside the field of view
+    pub fn in_fov(&self, angle_deg: f32) -> bool {
+        if self.fov_deg >= 360.0 {
+            return true;
+        }
+        let wrapped = (angle_deg + 180.0).rem_euclid(360.0) - 180.0;
+        wrapped.abs() <= self.fov_deg / 2.0
+    }
+}
+
 /// One angle-distance pair from a LIDAR scan (realistic sensor output)
 #[derive(Debug, Clone)]
 pub struct LidarHit {
+    /// Beam angle in the laser frame (0 = sensor forward, counter-clockwise)
     pub angle_deg: f32,
     pub distance: f32,
-
-    /// For visualisation only (in debug mode)
-    #[cfg(debug_assertions)]
-    pub debug: DebugHitInfo,
+    /// Return strength, 0 (nothing came back) to 1 (perfect diffuse reflector)
+    pub intensity: f32,
+    /// Sim time (seconds) at which the beam fired
+    pub stamp: f32,
 }
 
-/// For debug visualisation only (not included in release builds)
-#[cfg(debug_assertions)]
-#[derive(Debug, Clone)]
-pub struct DebugHitInfo {
-    pub hit_point: Vec2,
+impl LidarHit {
+    /// End of the beam in the laser frame
+    pub fn local_point(&self) -> Vec2 {
+        Vec2::from_angle(self.angle_deg.to_radians()) * self.distance
+    }
 }
 
 /// Tracks partial sweep state — emits current-frame rays only
@@ -30,3 +88,65 @@ pub struct LidarEmitter {
     /// This frame’s emitted rays (cleared each frame)
     pub hits: Vec<LidarHit>,
 }
+
+/// Imperfections applied to each beam (all zero = ideal sensor).
+///
+/// Draws come from the seeded "lidar_noise" stream, so noisy runs stay reproducible.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct LidarNoise {
+    /// Std-dev of the range error as a fraction of the true distance
+    pub range_sigma_fraction: f32,
+    /// Chance a beam produces no reading at all
+    pub dropout_prob: f32,
+    /// Chance a beam reports max range even though it hit something
+    pub spurious_max_range_prob: f32,
+    /// Std-dev of the error between reported and actual beam angle (degrees)
+    pub angle_jitter_deg: f32,
+}
+
+impl LidarNoise {
+    /// Roughly a budget triangulation LiDAR
+    pub fn typical() -> Self {
+        Self {
+            range_sigma_fraction: 0.02,
+            dropout_prob: 0.03,
+            spurious_max_range_prob: 0.01,
+            angle_jitter_deg: 0.5,
+        }
+    }
+}
+
+/// One full revolution of a LIDAR, laid out like a ROS `sensor_msgs/LaserScan`.
+///
+/// Angles are in radians in the laser frame; beam `i` points at
+/// `angle_min + i * angle_increment`. Bins that got no reading (outside the
+/// FOV, dropouts, blind zone, no return within range) hold `f32::INFINITY` and a NaN timestamp.
+#[derive(Event, Debug, Clone)]
+pub struct LaserScan {
+    pub entity: Entity,
+    /// Sim time of the first beam
+    pub stamp: f32,
+    pub angle_min: f32,
+    pub angle_increment: f32,
+    pub range_min: f32,
+    pub range_max: f32,
+    pub ranges: Vec<f32>,
+    pub intensities: Vec<f32>,
+    /// Per-beam offset from `stamp` (seconds)
+    pub timestamps: Vec<f32>,
+    /// Robot pose in the map (via the transform tree) at the first and last beam
+    pub start_pose: Pose2d,
+    pub end_pose: Pose2d,
+    /// Whether `ranges` carry the robot's motion during the sweep
+    pub motion_distorted: bool,
+}
+
+/// Collects `LidarEmitter` hits into the revolution in progress
+#[derive(Component, Debug, Clone, Default)]
+pub struct LaserScanAccumulator {
+    /// Laser angle of the last hit taken in; a smaller one starts a new revolution
+    pub last_angle_deg: Option<f32>,
+    pub scan: Option<LaserScan>,
+    /// Laser pose in the odom frame at the first beam (the deskew target)
+    pub start_laser_pose: Pose2d,
+}
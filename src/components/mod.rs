@@ -1,4 +1,9 @@
+pub mod bumper;
+pub mod cliff;
 pub mod cmd_vel;
 pub mod collectible;
+pub mod frames;
+pub mod landmark;
 pub mod lidar;
 pub mod occupancy_grid;
+pub mod odometry;
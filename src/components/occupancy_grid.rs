@@ -1,5 +1,11 @@
 use bevy::prelude::*;
 
+use crate::constants::{
+    OCCUPANCY_CHUNK_CELLS, OCCUPANCY_CHUNK_DIRECTORY_MARGIN, OCCUPANCY_LOG_ODDS_FREE,
+    OCCUPANCY_LOG_ODDS_HIT, OCCUPANCY_LOG_ODDS_MAX, OCCUPANCY_LOG_ODDS_MIN,
+    OCCUPANCY_LOG_ODDS_MISS, OCCUPANCY_LOG_ODDS_OCCUPIED,
+};
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub enum CellState {
     Unknown,
@@ -7,79 +13,482 @@ pub enum CellState {
     Solid,
 }
 
-#[derive(Component)]
+/// Auto-nav ran out of frontiers and cleared the free cells of this robot's
+/// grid (solids are kept) to explore again
+#[derive(Event, Debug, Clone, Copy)]
+pub struct OccupancyGridReset {
+    pub entity: Entity,
+}
+
+/// Cells of this robot's grid whose `CellState` changed during the last map
+/// update, so the renderer and planner only revisit that region
+#[derive(Event, Debug, Clone, Copy)]
+pub struct OccupancyGridUpdated {
+    pub entity: Entity,
+    /// Bounding box of the changed cells (inclusive)
+    pub region: IRect,
+}
+
+/// How sensor evidence accumulates in an `OccupancyGrid` (log-odds units)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogOddsModel {
+    /// Added to a cell a beam ends in
+    pub hit: f32,
+    /// Added to each cell a beam passes through (negative)
+    pub miss: f32,
+    /// Bounds on a cell's log-odds
+    pub min: f32,
+    pub max: f32,
+    /// A cell reads `Solid` at or above this, `Free` at or below `free`
+    pub occupied: f32,
+    pub free: f32,
+}
+
+impl Default for LogOddsModel {
+    fn default() -> Self {
+        Self {
+            hit: OCCUPANCY_LOG_ODDS_HIT,
+            miss: OCCUPANCY_LOG_ODDS_MISS,
+            min: OCCUPANCY_LOG_ODDS_MIN,
+            max: OCCUPANCY_LOG_ODDS_MAX,
+            occupied: OCCUPANCY_LOG_ODDS_OCCUPIED,
+            free: OCCUPANCY_LOG_ODDS_FREE,
+        }
+    }
+}
+
+impl LogOddsModel {
+    /// Thresholded view of a log-odds value
+    pub fn state(&self, log_odds: f32) -> CellState {
+        if log_odds >= self.occupied {
+            CellState::Solid
+        } else if log_odds <= self.free {
+            CellState::Free
+        } else {
+            CellState::Unknown
+        }
+    }
+
+    /// Log-odds that reads as `state` with full confidence
+    pub fn log_odds(&self, state: CellState) -> f32 {
+        match state {
+            CellState::Unknown => 0.0,
+            CellState::Free => self.min,
+            CellState::Solid => self.max,
+        }
+    }
+}
+
+/// Probabilistic occupancy grid: each cell holds the log-odds that it is
+/// occupied (0 = unknown), so repeated observations outvote noise and a
+/// cell can turn back to free when an obstacle moves away.
+///
+/// The grid is unbounded and sparse: cells are stored in square chunks of
+/// `OCCUPANCY_CHUNK_CELLS`, allocated the first time something is written
+/// in them, so memory follows the explored area. Cell coordinates may be
+/// negative; cell (0, 0) has its lower-left corner at `origin`.
+///
+/// `get_cell` returns the thresholded `CellState` most callers want.
+#[derive(Component, Debug, Clone)]
 pub struct OccupancyGrid {
     pub resolution: f32, // pixels per cell
-    pub width: usize,
-    pub height: usize,
-    pub origin: Vec2,          // world-space origin of (0,0) in grid
-    pub cells: Vec<CellState>, // flat grid: y * width + x
+    pub origin: Vec2,    // world-space origin of (0,0) in grid
+    pub model: LogOddsModel,
+    /// Cells whose `CellState` changed since the last `take_dirty` (inclusive)
+    pub dirty: Option<IRect>,
+    chunks: Chunks,
 }
 
 impl OccupancyGrid {
-    pub fn new(width: usize, height: usize, resolution: f32, origin: Vec2) -> Self {
+    pub fn new(resolution: f32, origin: Vec2) -> Self {
         Self {
             resolution,
-            width,
-            height,
             origin,
-            cells: vec![CellState::Unknown; width * height],
+            model: LogOddsModel::default(),
+            dirty: None,
+            chunks: Chunks::default(),
         }
     }
 
-    pub fn index(&self, x: usize, y: usize) -> usize {
-        y * self.width + x
+    pub fn with_model(mut self, model: LogOddsModel) -> Self {
+        self.model = model;
+        self
     }
 
-    pub fn set(&mut self, x: usize, y: usize, state: CellState) {
-        if x < self.width && y < self.height {
-            let idx = self.index(x, y);
-            self.cells[idx] = state;
-        }
+    /// Thresholded state of a cell. Always `Some`: the grid is unbounded, so
+    /// a cell nothing observed is `Unknown` rather than missing.
+    pub fn get_cell(&self, cell: IVec2) -> Option<CellState> {
+        Some(self.state_at(cell))
     }
 
-    pub fn get(&self, x: usize, y: usize) -> Option<CellState> {
-        if x < self.width && y < self.height {
-            Some(self.cells[self.index(x, y)])
-        } else {
-            None
-        }
+    /// Thresholded state of a cell (`Unknown` where nothing was observed)
+    pub fn state_at(&self, cell: IVec2) -> CellState {
+        self.model.state(self.cell_log_odds(cell))
     }
 
-    /// New: get using IVec2
-    pub fn get_cell(&self, cell: IVec2) -> Option<CellState> {
-        let x = cell.x;
-        let y = cell.y;
-        if x >= 0 && y >= 0 {
-            self.get(x as usize, y as usize)
-        } else {
-            None
+    /// Overwrites a cell with full confidence in `state`
+    pub fn set_cell(&mut self, cell: IVec2, state: CellState) {
+        let value = self.model.log_odds(state);
+        self.write(cell, value);
+    }
+
+    /// Log-odds that `cell` is occupied
+    pub fn cell_log_odds(&self, cell: IVec2) -> f32 {
+        let (chunk, index) = chunk_index(cell);
+        self.chunks.get(chunk).map_or(0.0, |values| values[index])
+    }
+
+    /// Probability that `cell` is occupied
+    pub fn occupancy_probability(&self, cell: IVec2) -> f32 {
+        1.0 - 1.0 / (1.0 + self.cell_log_odds(cell).exp())
+    }
+
+    /// Adds the evidence of a beam ending in `cell`
+    pub fn observe_hit(&mut self, cell: IVec2) {
+        self.add_log_odds(cell, self.model.hit);
+    }
+
+    /// Adds the evidence of a beam passing through `cell`
+    pub fn observe_miss(&mut self, cell: IVec2) {
+        self.add_log_odds(cell, self.model.miss);
+    }
+
+    fn add_log_odds(&mut self, cell: IVec2, delta: f32) {
+        let value = (self.cell_log_odds(cell) + delta).clamp(self.model.min, self.model.max);
+        self.write(cell, value);
+    }
+
+    /// Stores a cell's log-odds (allocating its chunk), noting a change of state
+    fn write(&mut self, cell: IVec2, value: f32) {
+        let (chunk, index) = chunk_index(cell);
+        let values = self.chunks.get_or_insert(chunk);
+        let changed = self.model.state(values[index]) != self.model.state(value);
+        values[index] = value;
+        if changed {
+            self.mark_dirty(IRect::from_corners(cell, cell));
         }
     }
 
-    /// New: get using IVec2
-    pub fn set_cell(&mut self, cell: IVec2, state: CellState) {
-        let x = cell.x;
-        let y = cell.y;
-        if x >= 0 && y >= 0 {
-            self.set(x as usize, y as usize, state)
+    /// Adds `region` to the cells reported by the next `take_dirty`
+    pub fn mark_dirty(&mut self, region: IRect) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(region),
+            None => region,
+        });
+    }
+
+    /// The region changed since the last call, if any
+    pub fn take_dirty(&mut self) -> Option<IRect> {
+        self.dirty.take()
+    }
+
+    /// Cells covered by the allocated chunks (inclusive), if any
+    pub fn bounds(&self) -> Option<IRect> {
+        let mut chunks = self.chunks.iter().map(|(chunk, _)| chunk);
+        let first = chunks.next()?;
+        let (min, max) = chunks.fold((first, first), |(min, max), chunk| {
+            (min.min(chunk), max.max(chunk))
+        });
+        Some(IRect::from_corners(
+            min * OCCUPANCY_CHUNK_CELLS,
+            (max + IVec2::ONE) * OCCUPANCY_CHUNK_CELLS - IVec2::ONE,
+        ))
+    }
+
+    /// Chunks allocated so far
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.iter().count()
+    }
+
+    /// Every allocated cell with its log-odds, in no particular order
+    pub fn cells(&self) -> impl Iterator<Item = (IVec2, f32)> + '_ {
+        self.chunks.iter().flat_map(|(chunk, values)| {
+            let corner = chunk * OCCUPANCY_CHUNK_CELLS;
+            values.iter().enumerate().map(move |(index, value)| {
+                let local = IVec2::new(
+                    index as i32 % OCCUPANCY_CHUNK_CELLS,
+                    index as i32 / OCCUPANCY_CHUNK_CELLS,
+                );
+                (corner + local, *value)
+            })
+        })
+    }
+
+    /// Every cell the segment `from` → `to` (world) passes through, in
+    /// order: an exact grid walk (Amanatides & Woo), taking both neighbours
+    /// where it crosses a corner (supercover) so diagonal beams skip nothing.
+    /// The last cell holds `to`.
+    pub fn beam_cells(&self, from: Vec2, to: Vec2) -> Vec<IVec2> {
+        let start = (from - self.origin) / self.resolution;
+        let end = (to - self.origin) / self.resolution;
+        let mut cell = start.floor().as_ivec2();
+        let end_cell = end.floor().as_ivec2();
+        let delta = end - start;
+        // (`signum` would make an axis the beam runs along step too)
+        let sign = |d: f32| (d > 0.0) as i32 - (d < 0.0) as i32;
+        let step = IVec2::new(sign(delta.x), sign(delta.y));
+
+        // Beam parameter (0 at `from`, 1 at `to`) of the next x / y cell
+        // boundary, and between successive ones
+        let first_crossing = |position: f32, cell: i32, step: i32, delta: f32| match step {
+            0 => f32::INFINITY,
+            1 => (cell as f32 + 1.0 - position) / delta,
+            _ => (cell as f32 - position) / delta,
+        };
+        let mut t_max = Vec2::new(
+            first_crossing(start.x, cell.x, step.x, delta.x),
+            first_crossing(start.y, cell.y, step.y, delta.y),
+        );
+        let t_delta = Vec2::new(1.0 / delta.x.abs(), 1.0 / delta.y.abs());
+
+        let mut cells = vec![cell];
+        let offset = (end_cell - cell).abs();
+        let mut remaining = offset.x + offset.y;
+        while remaining > 0 {
+            let corner = step.x != 0
+                && step.y != 0
+                && (t_max.x - t_max.y).abs() <= f32::EPSILON * t_max.x.abs().max(1.0);
+            if corner && remaining >= 2 {
+                cells.push(cell + IVec2::new(step.x, 0));
+                cells.push(cell + IVec2::new(0, step.y));
+                cell += step;
+                t_max += t_delta;
+                remaining -= 2;
+            } else if t_max.x < t_max.y {
+                cell.x += step.x;
+                t_max.x += t_delta.x;
+                remaining -= 1;
+            } else {
+                cell.y += step.y;
+                t_max.y += t_delta.y;
+                remaining -= 1;
+            }
+            cells.push(cell);
         }
+        cells
     }
 
-    /// Converts from world position to grid cell
+    /// Converts from world position to grid cell (`None` only for a
+    /// non-finite position: the grid grows to cover any other)
     pub fn world_to_cell(&self, pos: Vec2) -> Option<IVec2> {
         let rel = (pos - self.origin) / self.resolution;
-        let x = rel.x.floor() as i32;
-        let y = rel.y.floor() as i32;
-        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
-            Some(IVec2::new(x, y))
-        } else {
-            None
-        }
+        rel.is_finite().then(|| rel.floor().as_ivec2())
     }
 
     /// Converts from grid cell to world center position
     pub fn cell_to_world(&self, cell: IVec2) -> Vec2 {
         self.origin + (cell.as_vec2() + Vec2::splat(0.5)) * self.resolution
     }
+
+    /// Forgets everything (all cells `Unknown`); the chunks stay allocated,
+    /// ready for the same area to be mapped again
+    pub fn clear(&mut self) {
+        for values in self.chunks.values_mut() {
+            values.fill(0.0);
+        }
+        if let Some(bounds) = self.bounds() {
+            self.mark_dirty(bounds);
+        }
+    }
+
+    /// Forgets explored space, keeping the walls (`Free` → `Unknown`)
+    pub fn clear_free(&mut self) {
+        for values in self.chunks.values_mut() {
+            for log_odds in values.iter_mut() {
+                if *log_odds <= self.model.free {
+                    *log_odds = 0.0;
+                }
+            }
+        }
+        if let Some(bounds) = self.bounds() {
+            self.mark_dirty(bounds);
+        }
+    }
+}
+
+/// The chunks of an `OccupancyGrid`: a directory laid out densely over a
+/// rectangle of chunk coordinates, which grows to take in new ones. Only
+/// chunks written to hold cells, so the directory costs a pointer per
+/// chunk while lookups stay plain indexing.
+#[derive(Debug, Clone, Default)]
+struct Chunks {
+    /// Chunk coordinates the directory covers (inclusive), once anything is written
+    rect: Option<IRect>,
+    /// Row-major over `rect`; each chunk row-major inside
+    slots: Vec<Option<Box<[f32]>>>,
+}
+
+impl Chunks {
+    fn slot(&self, chunk: IVec2) -> Option<usize> {
+        let rect = self.rect.filter(|rect| rect.contains(chunk))?;
+        let offset = chunk - rect.min;
+        Some((offset.y * (rect.width() + 1) + offset.x) as usize)
+    }
+
+    fn get(&self, chunk: IVec2) -> Option<&[f32]> {
+        self.slots[self.slot(chunk)?].as_deref()
+    }
+
+    /// The chunk's cells, allocating them (all unknown) on first use
+    fn get_or_insert(&mut self, chunk: IVec2) -> &mut [f32] {
+        let slot = match self.slot(chunk) {
+            Some(slot) => slot,
+            None => self.grow_to(chunk),
+        };
+        self.slots[slot].get_or_insert_with(|| vec![0.0; CHUNK_AREA].into_boxed_slice())
+    }
+
+    /// Re-lays the directory to cover `chunk` (with room to spare around
+    /// it, so a robot driving on doesn't re-lay it every chunk); returns
+    /// the chunk's slot
+    fn grow_to(&mut self, chunk: IVec2) -> usize {
+        let margin = IVec2::splat(OCCUPANCY_CHUNK_DIRECTORY_MARGIN);
+        let wanted = IRect::from_corners(chunk - margin, chunk + margin);
+        let old = std::mem::take(self);
+        self.rect = Some(old.rect.map_or(wanted, |rect| rect.union(wanted)));
+        let size = self
+            .rect
+            .map_or(IVec2::ZERO, |rect| rect.size() + IVec2::ONE);
+        self.slots = vec![None; (size.x * size.y) as usize];
+        for (position, values) in old.into_chunks() {
+            if let Some(slot) = self.slot(position) {
+                self.slots[slot] = Some(values);
+            }
+        }
+        self.slot(chunk).unwrap_or_default()
+    }
+
+    /// Allocated chunks with their coordinates
+    fn iter(&self) -> impl Iterator<Item = (IVec2, &[f32])> + '_ {
+        self.positions()
+            .zip(self.slots.iter())
+            .filter_map(|(position, values)| Some((position, values.as_deref()?)))
+    }
+
+    fn values_mut(&mut self) -> impl Iterator<Item = &mut Box<[f32]>> + '_ {
+        self.slots.iter_mut().flatten()
+    }
+
+    fn into_chunks(self) -> impl Iterator<Item = (IVec2, Box<[f32]>)> {
+        self.positions()
+            .zip(self.slots)
+            .filter_map(|(position, values)| Some((position, values?)))
+    }
+
+    /// Chunk coordinates of each slot, in order
+    fn positions(&self) -> impl Iterator<Item = IVec2> {
+        let rect = self.rect.unwrap_or_default();
+        (rect.min.y..=rect.max.y)
+            .flat_map(move |y| (rect.min.x..=rect.max.x).map(move |x| IVec2::new(x, y)))
+    }
+}
+
+const CHUNK_AREA: usize = (OCCUPANCY_CHUNK_CELLS * OCCUPANCY_CHUNK_CELLS) as usize;
+
+/// The chunk holding `cell`, and the cell's index inside it
+fn chunk_index(cell: IVec2) -> (IVec2, usize) {
+    let size = IVec2::splat(OCCUPANCY_CHUNK_CELLS);
+    let local = cell.rem_euclid(size);
+    (
+        cell.div_euclid(size),
+        (local.y * OCCUPANCY_CHUNK_CELLS + local.x) as usize,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 10 px cells with the origin at the world origin
+    fn grid() -> OccupancyGrid {
+        OccupancyGrid::new(10.0, Vec2::ZERO)
+    }
+
+    fn cells(list: &[(i32, i32)]) -> Vec<IVec2> {
+        list.iter().map(|&(x, y)| IVec2::new(x, y)).collect()
+    }
+
+    #[test]
+    fn beam_along_an_axis_visits_each_cell_once() {
+        let grid = grid();
+        assert_eq!(
+            grid.beam_cells(Vec2::new(5.0, 5.0), Vec2::new(45.0, 5.0)),
+            cells(&[(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)])
+        );
+        assert_eq!(
+            grid.beam_cells(Vec2::new(5.0, 5.0), Vec2::new(5.0, -25.0)),
+            cells(&[(0, 0), (0, -1), (0, -2), (0, -3)])
+        );
+    }
+
+    #[test]
+    fn beam_across_the_origin_reaches_negative_cells() {
+        let grid = grid();
+        assert_eq!(
+            grid.beam_cells(Vec2::new(25.0, 5.0), Vec2::new(-15.0, 5.0)),
+            cells(&[(2, 0), (1, 0), (0, 0), (-1, 0), (-2, 0)])
+        );
+    }
+
+    #[test]
+    fn diagonal_beam_between_corners_steps_one_axis_at_a_time() {
+        let grid = grid();
+        assert_eq!(
+            grid.beam_cells(Vec2::new(2.0, 5.0), Vec2::new(32.0, 35.0)),
+            cells(&[(0, 0), (0, 1), (1, 1), (1, 2), (2, 2), (2, 3), (3, 3)])
+        );
+    }
+
+    #[test]
+    fn beam_through_a_vertex_visits_both_neighbours() {
+        let grid = grid();
+        // Slope 1/2 through the vertices (10, 10) and (30, 20)
+        assert_eq!(
+            grid.beam_cells(Vec2::new(0.0, 5.0), Vec2::new(40.0, 25.0)),
+            cells(&[
+                (0, 0),
+                (1, 0),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (3, 1),
+                (2, 2),
+                (3, 2),
+                (4, 2)
+            ])
+        );
+    }
+
+    #[test]
+    fn diagonal_beam_through_negative_vertices_visits_both_neighbours() {
+        let grid = grid();
+        assert_eq!(
+            grid.beam_cells(Vec2::new(-5.0, -5.0), Vec2::new(-35.0, -35.0)),
+            cells(&[
+                (-1, -1),
+                (-2, -1),
+                (-1, -2),
+                (-2, -2),
+                (-3, -2),
+                (-2, -3),
+                (-3, -3),
+                (-4, -3),
+                (-3, -4),
+                (-4, -4)
+            ])
+        );
+    }
+
+    #[test]
+    fn beam_within_one_cell_is_that_cell() {
+        let grid = grid();
+        assert_eq!(
+            grid.beam_cells(Vec2::new(3.0, 3.0), Vec2::new(7.0, 8.0)),
+            cells(&[(0, 0)])
+        );
+        assert_eq!(
+            grid.beam_cells(Vec2::new(-5.0, -5.0), Vec2::new(-5.0, -5.0)),
+            cells(&[(-1, -1)])
+        );
+    }
 }
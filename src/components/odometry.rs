@@ -0,0 +1,84 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::components::frames::Pose2d;
+
+/// Imperfections of the wheel encoders (all zero = perfect dead reckoning).
+///
+/// Draws come from the seeded "odometry_noise" stream, so noisy runs stay reproducible.
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq)]
+pub struct WheelOdometry {
+    /// Std-dev of the per-step distance error as a fraction of the step (wheel slip)
+    pub slip_sigma_fraction: f32,
+    /// Std-dev of the per-step rotation error as a fraction of the turn
+    pub turn_slip_sigma_fraction: f32,
+    /// Systematic distance error, e.g. 0.02 = wheels 2% larger than assumed
+    pub scale_bias: f32,
+    /// Systematic heading drift per metre driven (degrees), from unequal wheels
+    pub yaw_bias_deg_per_m: f32,
+}
+
+impl WheelOdometry {
+    /// Roughly a cheap differential-drive base on a hard floor
+    pub fn typical() -> Self {
+        Self {
+            slip_sigma_fraction: 0.05,
+            turn_slip_sigma_fraction: 0.05,
+            scale_bias: 0.02,
+            yaw_bias_deg_per_m: 2.0,
+        }
+    }
+}
+
+/// The dead-reckoned pose, like a ROS `nav_msgs/Odometry` message.
+///
+/// `pose` is in the odom frame, which starts out on the hero's spawn pose
+/// in the map; it is `None` until the level is ready.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Odometry {
+    pub pose: Option<Pose2d>,
+    /// Measured forward speed (px/s)
+    pub linear: f32,
+    /// Measured turn rate (rad/s)
+    pub angular: f32,
+}
+
+/// Which pose the robot navigates and maps with
+#[derive(Component, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PoseSource {
+    /// The simulator's true pose
+    #[default]
+    GroundTruth,
+    /// Wheel odometry (drifts)
+    Odometry,
+}
+
+/// Recent odometry poses, for looking up where the robot was when a scan started
+#[derive(Debug, Clone, Default)]
+pub struct OdometryHistory {
+    /// (sim time, odometry pose), oldest first
+    poses: VecDeque<(f32, Pose2d)>,
+}
+
+impl OdometryHistory {
+    /// Adds the pose at sim time `now` and forgets anything older than `keep_secs`
+    pub fn record(&mut self, now: f32, pose: Pose2d, keep_secs: f32) {
+        self.poses.push_back((now, pose));
+        while self
+            .poses
+            .front()
+            .is_some_and(|(stamp, _)| now - stamp > keep_secs)
+        {
+            self.poses.pop_front();
+        }
+    }
+
+    /// The recorded pose closest to `stamp`
+    pub fn at(&self, stamp: f32) -> Option<Pose2d> {
+        self.poses
+            .iter()
+            .min_by(|a, b| (a.0 - stamp).abs().total_cmp(&(b.0 - stamp).abs()))
+            .map(|(_, pose)| *pose)
+    }
+}
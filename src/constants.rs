@@ -37,6 +37,13 @@ pub const LIDAR_MAX_RANGE_METERS: f32 = 1.0;
 /// Max LIDAR range in world pixels, based on METERS_PER_PIXEL.
 pub const LIDAR_MAX_RANGE_PX: f32 = LIDAR_MAX_RANGE_METERS / METERS_PER_PIXEL;
 
+/// Returns closer than this are discarded (blind zone around the sensor).
+pub const LIDAR_MIN_RANGE_METERS: f32 = 0.05;
+pub const LIDAR_MIN_RANGE_PX: f32 = LIDAR_MIN_RANGE_METERS / METERS_PER_PIXEL;
+
+/// Field of view of the scanner, centred on its forward axis (360 = full spin).
+pub const LIDAR_FOV_DEG: f32 = 360.0;
+
 // ===================
 // Occupancy Parameters
 // ===================
@@ -44,5 +51,27 @@ pub const LIDAR_MAX_RANGE_PX: f32 = LIDAR_MAX_RANGE_METERS / METERS_PER_PIXEL;
 // resolution in pixels (i.e. our world-coords) of our grid
 pub const OCCUPANCY_GRID_RES: f32 = 10.0;
 
-/// Beyond this distance, we treat LIDAR readings as inconclusive.
-pub const OCCUPANCY_ASSUMED_MAX_LIDAR_RANGE_PX: f32 = 0.9 * LIDAR_MAX_RANGE_PX; // (cheating a bit by coupling - but not using directly)
+/// Side of the square chunks the grid allocates as it grows, in cells
+pub const OCCUPANCY_CHUNK_CELLS: i32 = 32;
+
+/// Chunks of headroom the grid's chunk directory adds around a new chunk
+/// whenever it has to grow
+pub const OCCUPANCY_CHUNK_DIRECTORY_MARGIN: i32 = 4;
+
+/// Beyond this fraction of a sensor's max range, we treat LIDAR readings as inconclusive.
+pub const OCCUPANCY_ASSUMED_MAX_LIDAR_RANGE_FRACTION: f32 = 0.9;
+
+// Log-odds evidence per beam: a return adds HIT to its cell, every cell the
+// beam passes through adds MISS (logit(0.7) and logit(0.4))
+pub const OCCUPANCY_LOG_ODDS_HIT: f32 = 0.85;
+pub const OCCUPANCY_LOG_ODDS_MISS: f32 = -0.4;
+
+/// Clamp on the accumulated log-odds (probabilities 0.12 and 0.97), so the
+/// map can still change its mind about a cell when an obstacle moves.
+pub const OCCUPANCY_LOG_ODDS_MIN: f32 = -2.0;
+pub const OCCUPANCY_LOG_ODDS_MAX: f32 = 3.5;
+
+/// Log-odds at or above which a cell reads `Solid` (p ≈ 0.65), and at or
+/// below which it reads `Free` (p = 0.4, i.e. one miss)
+pub const OCCUPANCY_LOG_ODDS_OCCUPIED: f32 = 0.6;
+pub const OCCUPANCY_LOG_ODDS_FREE: f32 = -0.4;
@@ -14,6 +14,71 @@ pub fn real_start() {
     crate::app::build_app().run();
 }
 
+/// Options for native runs (parsed from the command line in `main.rs`)
+#[derive(Default)]
+pub struct NativeOptions {
+    pub headless: bool,
+    pub seed: Option<u64>,
+    pub timing: SimTiming,
+    /// Level descriptor path relative to `assets/` (default level if `None`)
+    pub level: Option<String>,
+    pub sensors: HeroSensors,
+    /// `map_server` YAML of a map to start from (also localized against)
+    pub map: Option<String>,
+}
+
+/// Native entry point: optionally headless, optionally deterministic (seeded).
+pub fn native_start(options: NativeOptions) {
+    let mut app = if options.headless {
+        crate::app::build_headless_app()
+    } else {
+        crate::app::build_app()
+    };
+
+    app.insert_resource(options.timing);
+    app.insert_resource(options.sensors);
+    if let Some(level) = options.level {
+        app.insert_resource(LevelSelection(level));
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(map) = options.map.as_deref().and_then(load_map) {
+        app.insert_resource(KnownMap(map));
+    }
+    if let Some(seed) = options.seed {
+        make_deterministic(&mut app, seed);
+    }
+
+    app.run();
+}
+
+pub use app::{build_app, build_app_with_mode, build_headless_app, SimMode};
+pub use bundles::hero::HeroSensors;
+pub use components::frames::{Pose2d, TransformTree};
+pub use components::landmark::{LandmarkDetector, LandmarkObservation};
+pub use components::lidar::{LaserScan, LidarConfig, LidarNoise};
+pub use components::occupancy_grid::{
+    CellState, LogOddsModel, OccupancyGrid, OccupancyGridUpdated,
+};
+pub use components::odometry::{Odometry, PoseSource, WheelOdometry};
+pub use plugins::localization::amcl::{AmclFilter, AmclPose};
+pub use plugins::localization::likelihood_field::KnownMap;
+pub use plugins::localization::localization_plugin::Localization;
+pub use plugins::map_server::map_file::{format_map, parse_map, MapMetadata, MapMode};
+#[cfg(not(target_arch = "wasm32"))]
+pub use plugins::map_server::map_file::{load_map, save_map};
+pub use plugins::map_server::map_server_plugin::{LoadMapRequest, SaveMapRequest};
+pub use plugins::sim::kidnap::{KidnapRequest, Kidnapped};
+pub use plugins::sim::sim_plugin::make_deterministic;
+pub use plugins::sim::sim_time::{SimSpeed, SimTiming};
+pub use plugins::slam::ekf_slam::EkfSlam;
+pub use plugins::slam::pose_graph::PoseGraph;
+pub use plugins::slam::scan_matcher::{ScanMatchPose, ScanMatcher};
+pub use plugins::slam::slam_plugin::Slam;
+pub use systems::level_descriptor::LevelSelection;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod batch;
+
 mod app;
 mod bundles;
 mod components;
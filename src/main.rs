@@ -5,5 +5,65 @@ fn main() {
 
 #[cfg(not(target_arch = "wasm32"))]
 fn main() {
-    pick_e::real_start();
+    // Usage: pick-e [--headless] [--seed <u64>] [--rate <hz>] [--speed <pause|1|10|max>]
+    //               [--level <levels/name.level.ron>] [--lidar-noise] [--odometry] [--amcl]
+    //               [--slam] [--pose-graph] [--ekf-slam] [--map <maps/name.yaml>]
+    //        pick-e --rebuild-caches   (regenerate every level's collision cache, then exit)
+    let args: Vec<String> = std::env::args().collect();
+    let value_of = |flag: &str| {
+        args.iter()
+            .position(|arg| arg == flag)
+            .and_then(|i| args.get(i + 1))
+    };
+
+    if args.iter().any(|arg| arg == "--rebuild-caches") {
+        let rebuilt = pick_e::batch::rebuild_collision_caches();
+        println!(
+            "Rebuilt {} collision cache(s): {:?}",
+            rebuilt.len(),
+            rebuilt
+        );
+        return;
+    }
+
+    let mut options = pick_e::NativeOptions {
+        headless: args.iter().any(|arg| arg == "--headless"),
+        seed: value_of("--seed").and_then(|s| s.parse::<u64>().ok()),
+        level: value_of("--level").cloned(),
+        // Start from a saved map_server map (e.g. one saved with O)
+        map: value_of("--map").cloned(),
+        ..Default::default()
+    };
+    if args.iter().any(|arg| arg == "--lidar-noise") {
+        options.sensors.lidar_noise = pick_e::LidarNoise::typical();
+    }
+    // Map and navigate on drifting wheel odometry instead of the true pose
+    if args.iter().any(|arg| arg == "--odometry") {
+        options.sensors.odometry = pick_e::WheelOdometry::typical();
+        options.sensors.pose_source = pick_e::PoseSource::Odometry;
+    }
+    // Localize against the level map with a particle filter
+    if args.iter().any(|arg| arg == "--amcl") {
+        options.sensors.localization = pick_e::Localization::Amcl;
+    }
+    // Correct odometry by matching each scan against the map being built
+    if args.iter().any(|arg| arg == "--slam") {
+        options.sensors.slam = pick_e::Slam::ScanMatching;
+    }
+    // ...plus a pose graph that closes loops and re-renders the map
+    if args.iter().any(|arg| arg == "--pose-graph") {
+        options.sensors.slam = pick_e::Slam::PoseGraph;
+    }
+    // Landmark EKF SLAM over the collectibles instead
+    if args.iter().any(|arg| arg == "--ekf-slam") {
+        options.sensors.slam = pick_e::Slam::Ekf;
+    }
+    if let Some(rate_hz) = value_of("--rate").and_then(|s| s.parse::<f64>().ok()) {
+        options.timing.rate_hz = rate_hz;
+    }
+    if let Some(speed) = value_of("--speed").and_then(|s| pick_e::SimSpeed::parse(s)) {
+        options.timing.speed = speed;
+    }
+
+    pick_e::native_start(options);
 }
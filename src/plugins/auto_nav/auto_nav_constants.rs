@@ -16,3 +16,9 @@ pub const AVOID_FWD_CONE_DEG: f32 = 35.0;
 
 // A* weighting
 pub const COST_NON_BAND_PENALTY: i32 = 4;
+
+// Hazard back-off (bumper / cliff sensor)
+pub const BACKOFF_BUMP_CONE_DEG: f32 = 45.0;
+pub const BACKOFF_SECS: f32 = 0.5;
+pub const BACKOFF_LIN: f32 = -0.5;
+pub const BACKOFF_ANG: f32 = 0.8;
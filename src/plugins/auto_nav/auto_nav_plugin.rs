@@ -1,8 +1,9 @@
 use super::{
-    follow_path_system::{clear_debug_markers_system, follow_path_system},
+    follow_path_system::{clear_debug_markers_system, follow_path_system, hazard_backoff_system},
     plan_frontier_path_system::plan_frontier_path_system,
     toggle_autonav_system::{toggle_autonav_system, AutoNavMode},
 };
+use crate::app::RobotSet;
 use bevy::prelude::*;
 
 // ┌────────────────────────────────────────────────────────────────────────────┐
@@ -24,10 +25,13 @@ use bevy::prelude::*;
 //    - Attaches a `PathPlan` component to the hero containing that path.
 //
 // ▶ 3. `follow_path_system` (follow_path.rs)
-//    - Runs each frame to follow the current `PathPlan`, if any.
+//    - Runs each fixed step to follow the current `PathPlan`, if any.
 //    - Converts next cell target to a heading and velocity command (`CmdVel`).
 //    - Uses local avoidance to steer around nearby walls using a virtual cone.
 //    - Stops or rotates in place if unsafe to proceed.
+//    - On a `BumpEvent` / `CliffEvent`, drops the plan and attaches a
+//      `HazardBackOff`, which `hazard_backoff_system` drives (reverse + turn
+//      away) before planning resumes.
 //
 // ▶ 4. `stop_when_done_system` (done_check.rs)
 //    - Detects when no unexplored frontier cells remain.
@@ -54,9 +58,20 @@ pub struct AutoNavPlugin;
 impl Plugin for AutoNavPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<AutoNavMode>()
-            .add_systems(PreUpdate, clear_debug_markers_system)
-            .add_systems(Update, toggle_autonav_system)
-            .add_systems(Update, plan_frontier_path_system)
-            .add_systems(Update, follow_path_system);
+            .add_systems(Update, toggle_autonav_system.in_set(RobotSet::Input))
+            .add_systems(
+                FixedUpdate,
+                plan_frontier_path_system.in_set(RobotSet::Plan),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    clear_debug_markers_system,
+                    follow_path_system,
+                    hazard_backoff_system,
+                )
+                    .chain()
+                    .in_set(RobotSet::Act),
+            );
     }
 }
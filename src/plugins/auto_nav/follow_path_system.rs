@@ -1,10 +1,15 @@
+use crate::app::SimMode;
 use crate::bundles::hero::HeroController;
+use crate::components::bumper::BumpEvent;
+use crate::components::cliff::CliffEvent;
 use crate::components::cmd_vel::CmdVel;
-use crate::components::occupancy_grid::OccupancyGrid;
+use crate::components::frames::TransformTree;
+use crate::components::occupancy_grid::{OccupancyGrid, OccupancyGridUpdated};
 use crate::constants::HERO_RADIUS_PX;
 use crate::plugins::auto_nav::auto_nav_constants::*;
-use crate::plugins::auto_nav::plan_frontier_path_system::{distance_to_solid_or_edge, PathPlan};
+use crate::plugins::auto_nav::plan_frontier_path_system::{distance_to_solid, PathPlan};
 use crate::plugins::auto_nav::toggle_autonav_system::AutoNavMode;
+use crate::plugins::sim::sim_metrics::SimMetrics;
 use bevy::prelude::*;
 
 #[derive(Component)]
@@ -21,27 +26,83 @@ pub fn clear_debug_markers_system(
 
 const ENABLE_DEBUG_INFO: bool = false;
 
+/// Reversing away from something the bumper or a cliff sensor found.
+/// While present, no new path is planned.
+#[derive(Component, Debug)]
+pub struct HazardBackOff {
+    pub remaining_secs: f32,
+    pub angular: f32,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn follow_path_system(
     mode: Res<AutoNavMode>,
+    sim_mode: Res<SimMode>,
+    mut metrics: ResMut<SimMetrics>,
     mut commands: Commands,
+    mut bumps: EventReader<BumpEvent>,
+    mut cliffs: EventReader<CliffEvent>,
+    mut grid_updates: EventReader<OccupancyGridUpdated>,
     mut query: Query<
         (
             Entity,
             &mut CmdVel,
             &mut PathPlan,
-            &GlobalTransform,
+            &TransformTree,
             &OccupancyGrid,
         ),
         With<HeroController>,
     >,
 ) {
+    let grid_updates: Vec<OccupancyGridUpdated> = grid_updates.read().copied().collect();
     if !mode.enabled {
         return;
     }
 
-    for (entity, mut cmd, mut path, xform, grid) in query.iter_mut() {
-        // get this bot's position, and check if it has any more path-cells to traverse:
-        let pos = xform.translation().truncate();
+    // Head-on bump or cliff: drop the plan and back away, turning from the side
+    // that found the hazard (the grid marks it solid for the next plan).
+    // Grazing bumps are left alone: wall-band paths run close enough to touch.
+    let hazards: Vec<(Entity, f32)> = bumps
+        .read()
+        .filter(|bump| {
+            let angle = bump.local_point.y.atan2(bump.local_point.x).to_degrees();
+            angle.abs() <= BACKOFF_BUMP_CONE_DEG
+        })
+        .map(|bump| {
+            let side = match (bump.left, bump.right) {
+                (true, false) => 1.0,
+                (false, true) => -1.0,
+                _ => bump.local_point.y,
+            };
+            (bump.entity, side)
+        })
+        .chain(
+            cliffs
+                .read()
+                .map(|cliff| (cliff.entity, cliff.local_point.y)),
+        )
+        .collect();
+    for &(entity, side) in &hazards {
+        if query.contains(entity) {
+            metrics.paths_invalidated += 1;
+        }
+        commands
+            .entity(entity)
+            .remove::<PathPlan>()
+            .insert(HazardBackOff {
+                remaining_secs: BACKOFF_SECS,
+                angular: -side.signum() * BACKOFF_ANG,
+            });
+    }
+
+    for (entity, mut cmd, mut path, tree, grid) in query.iter_mut() {
+        if hazards.iter().any(|&(e, _)| e == entity) {
+            continue;
+        }
+
+        // get this bot's position (as the map sees it), and check if it has any more path-cells to traverse:
+        let pose = tree.map_to_base();
+        let pos = pose.translation;
         let Some(next_cell) = path.cells.first() else {
             if ENABLE_DEBUG_INFO {
                 info!("[AutoNav] No path cells left — stopping.");
@@ -51,11 +112,16 @@ pub fn follow_path_system(
             continue;
         };
 
-        // Check if the current path is still viable
-        let is_path_blocked = path
-            .cells
+        // Check if the current path is still viable (only cells the map
+        // update touched can have turned solid)
+        let is_path_blocked = grid_updates
             .iter()
-            .any(|cell| distance_to_solid_or_edge(grid, *cell, 0) == 0);
+            .filter(|update| update.entity == entity)
+            .any(|update| {
+                path.cells.iter().any(|cell| {
+                    update.region.contains(*cell) && distance_to_solid(grid, *cell, 0) == 0
+                })
+            });
 
         if is_path_blocked {
             if ENABLE_DEBUG_INFO {
@@ -63,6 +129,7 @@ pub fn follow_path_system(
             }
             cmd.linear = 0.0;
             cmd.angular = 0.0;
+            metrics.paths_invalidated += 1;
             commands.entity(entity).remove::<PathPlan>();
             continue;
         }
@@ -72,17 +139,19 @@ pub fn follow_path_system(
         let to_target = target_pos - pos;
         let dist = to_target.length();
 
-        commands
-            .spawn(SpriteBundle {
-                sprite: Sprite {
-                    color: Color::RED,
-                    custom_size: Some(Vec2::splat(6.0)),
+        if sim_mode.is_windowed() {
+            commands
+                .spawn(SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::RED,
+                        custom_size: Some(Vec2::splat(6.0)),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(target_pos.extend(20.0)),
                     ..default()
-                },
-                transform: Transform::from_translation(target_pos.extend(20.0)),
-                ..default()
-            })
-            .insert(TemporaryDebugMarker);
+                })
+                .insert(TemporaryDebugMarker);
+        }
 
         if ENABLE_DEBUG_INFO {
             info!(
@@ -113,13 +182,14 @@ pub fn follow_path_system(
 
                 // Remove the component - to allow replanning
                 // You’ll need access to `Entity` and `Commands`
+                metrics.record_path_completed(&path);
                 commands.entity(entity).remove::<PathPlan>();
             }
 
             continue;
         }
 
-        let forward = xform.right().truncate().normalize_or_zero();
+        let forward = Vec2::from_angle(pose.yaw);
         let desired = to_target.normalize_or_zero();
 
         let forward_clear_ok = heading_clear_ok(
@@ -172,6 +242,25 @@ pub fn follow_path_system(
     }
 }
 
+/// Drives the back-off manoeuvre, then hands control back to the planner
+pub fn hazard_backoff_system(
+    time: Res<Time>,
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut CmdVel, &mut HazardBackOff)>,
+) {
+    for (entity, mut cmd, mut backoff) in query.iter_mut() {
+        if backoff.remaining_secs <= 0.0 {
+            cmd.linear = 0.0;
+            cmd.angular = 0.0;
+            commands.entity(entity).remove::<HazardBackOff>();
+            continue;
+        }
+        cmd.linear = BACKOFF_LIN;
+        cmd.angular = backoff.angular;
+        backoff.remaining_secs -= time.delta_seconds();
+    }
+}
+
 fn heading_clear_ok(
     grid: &OccupancyGrid,
     pos: Vec2,
@@ -196,7 +285,7 @@ fn heading_clearance_cells(grid: &OccupancyGrid, pos: Vec2, dir: Vec2) -> i32 {
     for i in 1..=AVOID_LOOKAHEAD_STEPS {
         let p = pos + dir * (i as f32) * step_world;
         if let Some(c) = grid.world_to_cell(p) {
-            let d = distance_to_solid_or_edge(grid, c, DIST_SCAN_MAX);
+            let d = distance_to_solid(grid, c, DIST_SCAN_MAX);
             min_clear = min_clear.min(d);
         } else {
             return 0;
@@ -1,46 +1,81 @@
 use bevy::prelude::*;
 use std::collections::{HashSet, VecDeque};
 
+use crate::app::SimMode;
 use crate::bundles::hero::HeroController;
-use crate::components::occupancy_grid::{CellState, OccupancyGrid};
+use crate::components::frames::TransformTree;
+use crate::components::occupancy_grid::{CellState, OccupancyGrid, OccupancyGridReset};
 use crate::plugins::auto_nav::auto_nav_constants::*;
+use crate::plugins::auto_nav::follow_path_system::HazardBackOff;
 use crate::plugins::auto_nav::toggle_autonav_system::{AutoNavMode, Phase};
+use crate::plugins::sim::sim_metrics::SimMetrics;
 
 #[derive(Component)]
 pub struct PathPlan {
     pub cells: Vec<IVec2>,
+    /// Length of the planned route in world pixels (one grid step per cell)
+    pub planned_length: f32,
+    /// Distance the hero has driven since this plan was attached
+    pub driven_length: f32,
+}
+
+impl PathPlan {
+    pub fn new(cells: Vec<IVec2>, resolution: f32) -> Self {
+        let planned_length = cells.len().saturating_sub(1) as f32 * resolution;
+        Self {
+            cells,
+            planned_length,
+            driven_length: 0.0,
+        }
+    }
 }
 
 #[derive(Component)]
 pub struct PathDebugMarker;
 
+#[allow(clippy::type_complexity)]
 pub fn plan_frontier_path_system(
     mut mode: ResMut<AutoNavMode>,
     mut commands: Commands,
     mut query: Query<
         (
             Entity,
-            &GlobalTransform,
+            &TransformTree,
             &mut OccupancyGrid,
             Option<&PathPlan>,
         ),
-        With<HeroController>,
+        (With<HeroController>, Without<HazardBackOff>),
     >,
     debug_markers: Query<Entity, With<PathDebugMarker>>,
+    sim_mode: Res<SimMode>,
+    mut metrics: ResMut<SimMetrics>,
+    mut resets: EventWriter<OccupancyGridReset>,
 ) {
     if !mode.enabled {
         return;
     }
 
-    for (entity, xform, mut grid, maybe_path) in query.iter_mut() {
+    for (entity, tree, mut grid, maybe_path) in query.iter_mut() {
         if maybe_path.is_some() {
             continue; // already has a plan
         }
 
-        let pos = xform.translation().truncate();
-        let Some(start_cell) = grid.world_to_cell(pos) else {
+        let pos = tree.map_to_base().translation;
+        let Some(robot_cell) = grid.world_to_cell(pos) else {
             continue;
         };
+        // Nothing mapped under the robot yet (a whole-scan mapper between
+        // scans, e.g. right after a reset): wait rather than give up
+        if grid.state_at(robot_cell) == CellState::Unknown {
+            continue;
+        }
+        // Hemmed in against a wall (e.g. the pose estimate drifted towards one):
+        // plan from the nearest safe cell, which the path then leads to first
+        let start_cell = if is_safe_cell(&grid, robot_cell, SAFE_MARGIN_MIN) {
+            robot_cell
+        } else {
+            nearest_safe_cell(&grid, robot_cell).unwrap_or(robot_cell)
+        };
 
         // --- Pick a target ---
         let target = match mode.phase {
@@ -78,40 +113,39 @@ pub fn plan_frontier_path_system(
                 },
             ) {
                 // Draw debug markers for waypoints
-                for cell in &path {
-                    let p = grid.cell_to_world(*cell);
-                    commands.spawn((
-                        SpriteBundle {
-                            transform: Transform::from_translation(p.extend(20.0)),
-                            sprite: Sprite {
-                                color: Color::rgba(0.2, 1.0, 0.4, 0.5),
-                                custom_size: Some(Vec2::splat(grid.resolution * 0.6)),
+                if sim_mode.is_windowed() {
+                    for cell in &path {
+                        let p = grid.cell_to_world(*cell);
+                        commands.spawn((
+                            SpriteBundle {
+                                transform: Transform::from_translation(p.extend(20.0)),
+                                sprite: Sprite {
+                                    color: Color::rgba(0.2, 1.0, 0.4, 0.5),
+                                    custom_size: Some(Vec2::splat(grid.resolution * 0.6)),
+                                    ..default()
+                                },
                                 ..default()
                             },
-                            ..default()
-                        },
-                        PathDebugMarker,
-                    ));
+                            PathDebugMarker,
+                        ));
+                    }
                 }
 
                 // Insert path
-                commands.entity(entity).insert(PathPlan { cells: path });
+                let plan = PathPlan::new(path, grid.resolution);
+                commands.entity(entity).insert(plan);
+                metrics.replans += 1;
             }
         } else {
             // No valid frontier found — reset the grid's explored area (keep solids)
             warn!("[AutoNav] No valid frontier remaining. Clearing grid and restarting...");
 
-            for y in 0..grid.height {
-                for x in 0..grid.width {
-                    let cell = IVec2::new(x as i32, y as i32);
-                    if grid.get_cell(cell) == Some(CellState::Free) {
-                        grid.set_cell(cell, CellState::Unknown);
-                    }
-                }
-            }
+            grid.clear_free();
+            resets.send(OccupancyGridReset { entity });
 
             // Reset phase to WallSweep
             mode.phase = Phase::WallSweep;
+            metrics.grid_resets += 1;
         }
     }
 }
@@ -129,15 +163,20 @@ fn find_nearest_frontier_where(
     visited.insert(start);
 
     while let Some(current) = queue.pop_front() {
-        if grid.get_cell(current) == Some(CellState::Free)
+        if grid.state_at(current) == CellState::Free
             && has_unknown_neighbor(grid, current)
             && predicate(current)
         {
             return Some(current);
         }
 
+        // Expand only where A* may step (free and clear of walls), so every
+        // frontier found here is one it can reach
         for n in neighbors4(current) {
-            if !visited.contains(&n) && grid.get_cell(n) == Some(CellState::Free) {
+            if !visited.contains(&n)
+                && grid.state_at(n) == CellState::Free
+                && is_safe_cell(grid, n, SAFE_MARGIN_MIN)
+            {
                 visited.insert(n);
                 queue.push_back(n);
             }
@@ -149,10 +188,32 @@ fn find_nearest_frontier_where(
 
 /* ---------------- Helpers ---------------- */
 
+/// Closest free cell clear of walls, searching outward through free cells
+fn nearest_safe_cell(grid: &OccupancyGrid, start: IVec2) -> Option<IVec2> {
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::new();
+    queue.push_back(start);
+    visited.insert(start);
+
+    while let Some(current) = queue.pop_front() {
+        if is_safe_cell(grid, current, SAFE_MARGIN_MIN) {
+            return Some(current);
+        }
+        for n in neighbors4(current) {
+            if !visited.contains(&n) && grid.state_at(n) == CellState::Free {
+                visited.insert(n);
+                queue.push_back(n);
+            }
+        }
+    }
+
+    None
+}
+
 pub fn has_unknown_neighbor(grid: &OccupancyGrid, cell: IVec2) -> bool {
     neighbors4(cell)
         .iter()
-        .any(|&n| grid.get_cell(n) == Some(CellState::Unknown))
+        .any(|&n| grid.state_at(n) == CellState::Unknown)
 }
 
 fn neighbors4(cell: IVec2) -> [IVec2; 4] {
@@ -165,19 +226,19 @@ fn neighbors4(cell: IVec2) -> [IVec2; 4] {
 }
 
 pub fn is_safe_cell(grid: &OccupancyGrid, cell: IVec2, safe_min: i32) -> bool {
-    let d = distance_to_solid_or_edge(grid, cell, DIST_SCAN_MAX);
+    let d = distance_to_solid(grid, cell, DIST_SCAN_MAX);
     d >= safe_min
 }
 
 fn is_wall_band_cell(grid: &OccupancyGrid, cell: IVec2, safe_min: i32, band_max: i32) -> bool {
-    let d = distance_to_solid_or_edge(grid, cell, DIST_SCAN_MAX);
+    let d = distance_to_solid(grid, cell, DIST_SCAN_MAX);
     d >= safe_min && d <= band_max
 }
 
-/// Manhattan-like local distance to nearest SOLID or map EDGE (edges treated as solid).
+/// Manhattan-like local distance to nearest SOLID cell (the grid has no edge).
 /// Returns a value in [0..=scan_max], where 0 means touching; scan_max+1 means beyond scan range.
-pub fn distance_to_solid_or_edge(grid: &OccupancyGrid, cell: IVec2, scan_max: i32) -> i32 {
-    if !in_bounds(grid, cell) || grid.get_cell(cell) == Some(CellState::Solid) {
+pub fn distance_to_solid(grid: &OccupancyGrid, cell: IVec2, scan_max: i32) -> i32 {
+    if grid.state_at(cell) == CellState::Solid {
         return 0;
     }
 
@@ -186,7 +247,7 @@ pub fn distance_to_solid_or_edge(grid: &OccupancyGrid, cell: IVec2, scan_max: i3
             let dx = r - dy.abs();
             for sx in [-1, 1] {
                 let c1 = cell + IVec2::new(sx * dx, dy);
-                if !in_bounds(grid, c1) || grid.get_cell(c1) == Some(CellState::Solid) {
+                if grid.state_at(c1) == CellState::Solid {
                     return r;
                 }
             }
@@ -195,10 +256,6 @@ pub fn distance_to_solid_or_edge(grid: &OccupancyGrid, cell: IVec2, scan_max: i3
     scan_max + 1
 }
 
-fn in_bounds(grid: &OccupancyGrid, c: IVec2) -> bool {
-    c.x >= 0 && c.y >= 0 && (c.x as usize) < grid.width && (c.y as usize) < grid.height
-}
-
 /* ---------------- A* ---------------- */
 
 #[derive(Clone, Copy)]
@@ -272,12 +329,12 @@ fn astar_with_policy(
         // Check each 4-connected neighbor
         for nb in neighbors4(pos) {
             // Only consider free cells
-            if grid.get_cell(nb) != Some(CellState::Free) {
+            if grid.state_at(nb) != CellState::Free {
                 continue;
             }
 
-            // Safety check — skip if too close to walls/edges
-            let dist = distance_to_solid_or_edge(grid, nb, DIST_SCAN_MAX);
+            // Safety check — skip if too close to walls
+            let dist = distance_to_solid(grid, nb, DIST_SCAN_MAX);
             if policy.avoid_unsafe && dist < policy.safe_min {
                 continue;
             }
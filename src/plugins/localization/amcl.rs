@@ -0,0 +1,394 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use super::likelihood_field::LikelihoodField;
+use super::localization_constants::*;
+use crate::components::frames::{wrap_angle, Pose2d, TransformTree};
+use crate::components::lidar::{LaserScan, LidarConfig};
+use crate::components::odometry::{Odometry, OdometryHistory, PoseSource};
+use crate::constants::METERS_PER_PIXEL;
+use crate::plugins::sim::sim_metrics::SimMetrics;
+use crate::plugins::sim::sim_seed::{gaussian, SimSeed};
+
+/// One pose hypothesis
+#[derive(Debug, Clone, Copy)]
+pub struct Particle {
+    /// In the map frame
+    pub pose: Pose2d,
+    pub weight: f32,
+}
+
+/// Monte Carlo localization state for one robot (as ROS `amcl`).
+///
+/// The filter is seeded around the robot's starting pose on the first step,
+/// then moved with odometry and weighed against each `LaserScan`.
+#[derive(Component, Debug, Clone, Default)]
+pub struct AmclFilter {
+    pub particles: Vec<Particle>,
+    /// Weighted mean of the particles at the last update (map frame)
+    pub estimate: Pose2d,
+    /// Covariance of `estimate` over (x, y, yaw); px², px·rad, rad²
+    pub covariance: [[f32; 3]; 3],
+    /// The correction the estimate implies: `map_to_odom * odom pose = estimate`
+    pub map_to_odom: Pose2d,
+    /// Odometry over the last `AMCL_ODOM_HISTORY_SECS`
+    odom_history: OdometryHistory,
+    /// Odometry pose the particles were last moved to
+    last_odom: Option<Pose2d>,
+    /// Motion since the last scan was weighed (px, rad)
+    moved: (f32, f32),
+    w_slow: f32,
+    w_fast: f32,
+}
+
+/// A localization result, like a ROS `geometry_msgs/PoseWithCovarianceStamped`
+#[derive(Event, Debug, Clone, Copy)]
+pub struct AmclPose {
+    pub entity: Entity,
+    /// Sim time of the scan the estimate is for
+    pub stamp: f32,
+    pub pose: Pose2d,
+    pub covariance: [[f32; 3]; 3],
+}
+
+/// Runs the particle filter for each new `LaserScan`.
+///
+/// With `PoseSource::Odometry` the resulting map → odom correction is written
+/// into the robot's transform tree, so mapping and navigation use the
+/// localized pose. The estimate's distance from the true pose goes into
+/// `SimMetrics`, along with how long the filter took to recover from a kidnap.
+#[allow(clippy::type_complexity)]
+#[allow(clippy::too_many_arguments)]
+pub fn amcl_system(
+    time: Res<Time>,
+    seed: Res<SimSeed>,
+    mut rng: Local<Option<StdRng>>,
+    field: Option<Res<LikelihoodField>>,
+    mut scans: EventReader<LaserScan>,
+    mut poses: EventWriter<AmclPose>,
+    mut metrics: ResMut<SimMetrics>,
+    mut query: Query<(
+        Entity,
+        &GlobalTransform,
+        &Odometry,
+        Option<&PoseSource>,
+        Option<&LidarConfig>,
+        &mut AmclFilter,
+        &mut TransformTree,
+    )>,
+) {
+    let scans: Vec<LaserScan> = scans.read().cloned().collect();
+    let Some(field) = field else {
+        return;
+    };
+    let rng = rng.get_or_insert_with(|| seed.rng("amcl"));
+    let now = time.elapsed_seconds();
+
+    for (entity, transform, odometry, source, config, mut filter, mut tree) in query.iter_mut() {
+        let Some(odom_pose) = odometry.pose else {
+            continue;
+        };
+
+        filter
+            .odom_history
+            .record(now, odom_pose, AMCL_ODOM_HISTORY_SECS);
+
+        // The odom frame starts out on the map pose, so that is where we begin
+        if filter.particles.is_empty() {
+            filter.initialise(odom_pose, rng);
+        }
+
+        let base_to_laser = config.copied().unwrap_or_default().base_to_laser();
+        for scan in scans.iter().filter(|scan| scan.entity == entity) {
+            // Deskewed scans are taken from where the revolution started
+            let odom_at_scan = filter.odom_history.at(scan.stamp).unwrap_or(odom_pose);
+            filter.predict(odom_at_scan, rng);
+
+            let (moved_trans, moved_rot) = filter.moved;
+            if moved_trans < AMCL_UPDATE_MIN_TRANS_PX
+                && moved_rot < AMCL_UPDATE_MIN_ROT_DEG.to_radians()
+            {
+                continue;
+            }
+            filter.moved = (0.0, 0.0);
+
+            filter.weigh(&field, scan, base_to_laser);
+            filter.resample(&field, rng);
+            filter.update_estimate();
+            filter.map_to_odom = filter.estimate * odom_at_scan.inverse();
+
+            poses.send(AmclPose {
+                entity,
+                stamp: scan.stamp,
+                pose: filter.estimate,
+                covariance: filter.covariance,
+            });
+        }
+
+        if source.copied().unwrap_or_default() == PoseSource::Odometry {
+            tree.map_to_odom = filter.map_to_odom;
+        }
+
+        let error = (filter.map_to_odom * odom_pose)
+            .translation
+            .distance(Pose2d::from_global(transform).translation);
+        metrics.localization_error_px = Some(error);
+        if let Some(kidnapped_at) = metrics.kidnapped_at_secs {
+            if metrics.kidnap_recovery_secs.is_none() && error < AMCL_RECOVERED_ERROR_PX {
+                metrics.kidnap_recovery_secs = Some(metrics.elapsed_secs - kidnapped_at);
+            }
+        }
+    }
+}
+
+impl AmclFilter {
+    fn initialise(&mut self, pose: Pose2d, rng: &mut StdRng) {
+        let weight = 1.0 / AMCL_PARTICLES as f32;
+        self.particles = (0..AMCL_PARTICLES)
+            .map(|_| Particle {
+                pose: Pose2d::new(
+                    pose.translation + Vec2::new(gaussian(rng), gaussian(rng)) * AMCL_INIT_SIGMA_PX,
+                    pose.yaw + gaussian(rng) * AMCL_INIT_SIGMA_YAW_DEG.to_radians(),
+                ),
+                weight,
+            })
+            .collect();
+        self.estimate = pose;
+        self.map_to_odom = Pose2d::IDENTITY;
+        self.last_odom = Some(pose);
+    }
+
+    /// Moves every particle by the odometry since the last call, with noise
+    /// (the "sample_motion_model_odometry" of Probabilistic Robotics)
+    fn predict(&mut self, odom: Pose2d, rng: &mut StdRng) {
+        let Some(last) = self.last_odom.replace(odom) else {
+            return;
+        };
+
+        let delta = odom.translation - last.translation;
+        let mut trans = delta.length();
+        // Turning on the spot: the direction of a tiny translation is noise
+        let mut rot1 = if trans < 0.5 {
+            0.0
+        } else {
+            wrap_angle(delta.y.atan2(delta.x) - last.yaw)
+        };
+        // Reversing (e.g. backing off a bump): drive backwards, don't spin round
+        if rot1.abs() > PI / 2.0 {
+            trans = -trans;
+            rot1 = wrap_angle(rot1 - PI);
+        }
+        let rot2 = wrap_angle(odom.yaw - last.yaw - rot1);
+        self.moved.0 += trans.abs();
+        self.moved.1 += wrap_angle(odom.yaw - last.yaw).abs();
+
+        let trans_m = trans.abs() * METERS_PER_PIXEL;
+        let rot1_sigma = AMCL_ALPHA_ROT_ROT * rot1.abs() + AMCL_ALPHA_ROT_TRANS * trans_m;
+        let trans_sigma = AMCL_ALPHA_TRANS_TRANS * trans.abs()
+            + AMCL_ALPHA_TRANS_ROT * (rot1.abs() + rot2.abs()) / METERS_PER_PIXEL;
+        let rot2_sigma = AMCL_ALPHA_ROT_ROT * rot2.abs() + AMCL_ALPHA_ROT_TRANS * trans_m;
+
+        for particle in self.particles.iter_mut() {
+            let rot1 = rot1 + rot1_sigma * gaussian(rng);
+            let trans = trans + trans_sigma * gaussian(rng);
+            let rot2 = rot2 + rot2_sigma * gaussian(rng);
+
+            let pose = &mut particle.pose;
+            pose.translation += Vec2::from_angle(pose.yaw + rot1) * trans;
+            pose.yaw = wrap_angle(pose.yaw + rot1 + rot2);
+        }
+    }
+
+    /// Multiplies each weight by the likelihood of the scan from that particle,
+    /// and tracks the average likelihood for recovery
+    fn weigh(&mut self, field: &LikelihoodField, scan: &LaserScan, base_to_laser: Pose2d) {
+        // Thin out the returns, not the beams: in open space few beams hit anything
+        let returns: Vec<Vec2> = scan
+            .ranges
+            .iter()
+            .enumerate()
+            .filter(|(_, range)| range.is_finite() && **range < scan.range_max)
+            .map(|(i, range)| {
+                Vec2::from_angle(scan.angle_min + i as f32 * scan.angle_increment) * *range
+            })
+            .collect();
+        let stride = returns.len().div_ceil(AMCL_BEAMS_PER_SCAN).max(1);
+        let beams: Vec<Vec2> = returns.into_iter().step_by(stride).collect();
+        if beams.is_empty() {
+            return;
+        }
+
+        let two_sigma_sq = 2.0 * AMCL_SIGMA_HIT_PX * AMCL_SIGMA_HIT_PX;
+        let log_likelihoods: Vec<f32> = self
+            .particles
+            .iter()
+            .map(|particle| {
+                let map_to_laser = particle.pose * base_to_laser;
+                beams
+                    .iter()
+                    .map(|beam| {
+                        let d = field.distance_at(map_to_laser.transform_point(*beam));
+                        (AMCL_Z_HIT * (-d * d / two_sigma_sq).exp() + AMCL_Z_RAND).ln()
+                    })
+                    .sum()
+            })
+            .collect();
+
+        // Per-beam likelihood, so the average doesn't depend on how many beams hit
+        let w_avg = log_likelihoods
+            .iter()
+            .map(|l| (l / beams.len() as f32).exp())
+            .sum::<f32>()
+            / self.particles.len() as f32;
+        if self.w_slow == 0.0 {
+            self.w_slow = w_avg;
+            self.w_fast = w_avg;
+        } else {
+            self.w_slow += AMCL_ALPHA_SLOW * (w_avg - self.w_slow);
+            self.w_fast += AMCL_ALPHA_FAST * (w_avg - self.w_fast);
+        }
+
+        let max = log_likelihoods
+            .iter()
+            .copied()
+            .fold(f32::NEG_INFINITY, f32::max);
+        for (particle, l) in self.particles.iter_mut().zip(&log_likelihoods) {
+            particle.weight *= (l - max).exp();
+        }
+        self.normalise();
+    }
+
+    /// Low-variance resampling once the weights have degenerated, injecting
+    /// random particles when the scans fit much worse than they used to
+    fn resample(&mut self, field: &LikelihoodField, rng: &mut StdRng) {
+        let ratio = if self.w_slow > 0.0 {
+            self.w_fast / self.w_slow
+        } else {
+            1.0
+        };
+        let inject_prob = if ratio < AMCL_RECOVERY_RATIO {
+            1.0 - ratio
+        } else {
+            0.0
+        };
+        let n_eff = 1.0
+            / self
+                .particles
+                .iter()
+                .map(|p| p.weight * p.weight)
+                .sum::<f32>();
+        if n_eff >= AMCL_RESAMPLE_NEFF_FRACTION * self.particles.len() as f32 && inject_prob == 0.0
+        {
+            return;
+        }
+
+        let n = self.particles.len();
+        let step = 1.0 / n as f32;
+        let mut target = rng.gen::<f32>() * step;
+        let mut cumulative = self.particles[0].weight;
+        let mut i = 0;
+        let mut resampled = Vec::with_capacity(n);
+        for _ in 0..n {
+            if !field.free_cells.is_empty() && rng.gen::<f32>() < inject_prob {
+                resampled.push(Particle {
+                    pose: random_pose(field, rng),
+                    weight: step,
+                });
+            } else {
+                while cumulative < target && i + 1 < n {
+                    i += 1;
+                    cumulative += self.particles[i].weight;
+                }
+                resampled.push(Particle {
+                    pose: self.particles[i].pose,
+                    weight: step,
+                });
+            }
+            target += step;
+        }
+        self.particles = resampled;
+    }
+
+    fn normalise(&mut self) {
+        let total: f32 = self.particles.iter().map(|p| p.weight).sum();
+        if total > 0.0 && total.is_finite() {
+            for particle in self.particles.iter_mut() {
+                particle.weight /= total;
+            }
+        } else {
+            let weight = 1.0 / self.particles.len() as f32;
+            for particle in self.particles.iter_mut() {
+                particle.weight = weight;
+            }
+        }
+    }
+
+    /// Weighted mean (circular for yaw) and covariance of the particles
+    fn update_estimate(&mut self) {
+        let mut mean = Vec2::ZERO;
+        let mut heading = Vec2::ZERO;
+        for particle in &self.particles {
+            mean += particle.pose.translation * particle.weight;
+            heading += Vec2::from_angle(particle.pose.yaw) * particle.weight;
+        }
+        let yaw = heading.y.atan2(heading.x);
+
+        let mut covariance = [[0.0; 3]; 3];
+        for particle in &self.particles {
+            let d = particle.pose.translation - mean;
+            let e = [d.x, d.y, wrap_angle(particle.pose.yaw - yaw)];
+            for (r, row) in covariance.iter_mut().enumerate() {
+                for (c, value) in row.iter_mut().enumerate() {
+                    *value += particle.weight * e[r] * e[c];
+                }
+            }
+        }
+
+        self.estimate = Pose2d::new(mean, yaw);
+        self.covariance = covariance;
+    }
+}
+
+fn random_pose(field: &LikelihoodField, rng: &mut StdRng) -> Pose2d {
+    let centre = field.free_cells[rng.gen_range(0..field.free_cells.len())];
+    let jitter = Vec2::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5)) * field.resolution;
+    Pose2d::new(centre + jitter, rng.gen_range(-PI..PI))
+}
+
+/// Draws each robot's particles, and its estimate with a 2σ position ellipse
+pub fn amcl_debug_draw_system(query: Query<&AmclFilter>, mut gizmos: Gizmos) {
+    for filter in query.iter() {
+        for particle in &filter.particles {
+            let tip = particle.pose.transform_point(Vec2::X * 8.0);
+            gizmos.line_2d(
+                particle.pose.translation,
+                tip,
+                Color::rgba(0.2, 0.6, 1.0, 0.6),
+            );
+        }
+
+        // Principal axes of the x/y block of the covariance
+        let [[xx, xy, _], [_, yy, _], _] = filter.covariance;
+        let half_trace = 0.5 * (xx + yy);
+        let spread = (0.25 * (xx - yy).powi(2) + xy * xy).sqrt();
+        let major = (half_trace + spread).max(0.0).sqrt();
+        let minor = (half_trace - spread).max(0.0).sqrt();
+        let angle = 0.5 * (2.0 * xy).atan2(xx - yy);
+
+        let estimate = filter.estimate;
+        gizmos.ellipse_2d(
+            estimate.translation,
+            angle,
+            Vec2::new(major, minor) * 2.0,
+            Color::YELLOW,
+        );
+        gizmos.line_2d(
+            estimate.translation,
+            estimate.transform_point(Vec2::X * 24.0),
+            Color::YELLOW,
+        );
+    }
+}
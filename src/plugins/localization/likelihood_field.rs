@@ -0,0 +1,175 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use super::localization_constants::AMCL_LIKELIHOOD_MAX_DIST_PX;
+use crate::components::occupancy_grid::{CellState, OccupancyGrid};
+use crate::systems::level::{ActiveLevel, LevelMask};
+
+/// A prior map to localize against, e.g. an `OccupancyGrid` saved from an
+/// earlier run. Without one, localization uses the level mask.
+#[derive(Resource)]
+pub struct KnownMap(pub OccupancyGrid);
+
+/// The known map prepared for the LiDAR likelihood-field model: for every
+/// cell, the distance to the nearest wall.
+///
+/// Cells are square, indexed from the bottom-left with Y up (like `OccupancyGrid`).
+#[derive(Resource, Debug, Clone)]
+pub struct LikelihoodField {
+    pub resolution: f32,
+    /// World position of cell (0, 0)'s centre
+    pub origin: Vec2,
+    pub width: usize,
+    pub height: usize,
+    /// Distance to the nearest wall per cell (px), capped at `AMCL_LIKELIHOOD_MAX_DIST_PX`
+    distances: Vec<f32>,
+    /// Centres of the free cells the robot could be in
+    pub free_cells: Vec<Vec2>,
+}
+
+impl LikelihoodField {
+    /// Walls are the mask's wall tiles; free space is what is reachable from the spawn
+    pub fn from_mask(mask: &LevelMask, spawn: Vec2) -> Self {
+        let reachable = mask
+            .find_tile_near(spawn)
+            .map(|start| mask.reachable_from(start))
+            .unwrap_or_else(|| vec![vec![false; mask.width]; mask.height]);
+
+        // Mask rows run top-down; flip them so cell Y points up
+        let mut occupied = vec![false; mask.width * mask.height];
+        let mut free_cells = Vec::new();
+        for (y, row) in reachable.iter().enumerate() {
+            for (x, is_reachable) in row.iter().enumerate() {
+                let up = mask.height - 1 - y;
+                occupied[up * mask.width + x] = mask.is_wall(x, y);
+                if *is_reachable {
+                    free_cells.push(mask.tile_to_world(x, y));
+                }
+            }
+        }
+
+        Self::build(
+            mask.width,
+            mask.height,
+            mask.tile_size,
+            mask.tile_to_world(0, mask.height.saturating_sub(1)),
+            occupied,
+            free_cells,
+        )
+    }
+
+    /// Walls are `Solid` cells; free space is every `Free` cell. The field
+    /// covers the grid's allocated chunks.
+    pub fn from_grid(grid: &OccupancyGrid) -> Self {
+        let bounds = grid.bounds().unwrap_or_default();
+        let (width, height) = (
+            (bounds.width() + 1) as usize,
+            (bounds.height() + 1) as usize,
+        );
+        let cells: Vec<IVec2> = (bounds.min.y..=bounds.max.y)
+            .flat_map(|y| (bounds.min.x..=bounds.max.x).map(move |x| IVec2::new(x, y)))
+            .collect();
+        let occupied = cells
+            .iter()
+            .map(|cell| grid.state_at(*cell) == CellState::Solid)
+            .collect();
+        let free_cells = cells
+            .iter()
+            .filter(|cell| grid.state_at(**cell) == CellState::Free)
+            .map(|cell| grid.cell_to_world(*cell))
+            .collect();
+
+        Self::build(
+            width,
+            height,
+            grid.resolution,
+            grid.cell_to_world(bounds.min),
+            occupied,
+            free_cells,
+        )
+    }
+
+    /// Brushfire outwards from every wall cell, carrying the nearest wall along
+    fn build(
+        width: usize,
+        height: usize,
+        resolution: f32,
+        origin: Vec2,
+        occupied: Vec<bool>,
+        free_cells: Vec<Vec2>,
+    ) -> Self {
+        let mut distances = vec![f32::INFINITY; width * height];
+        let mut nearest = vec![IVec2::ZERO; width * height];
+        let mut queue = VecDeque::new();
+
+        for (i, _) in occupied.iter().enumerate().filter(|(_, wall)| **wall) {
+            distances[i] = 0.0;
+            nearest[i] = IVec2::new((i % width) as i32, (i / width) as i32);
+            queue.push_back(i);
+        }
+
+        while let Some(i) = queue.pop_front() {
+            let cell = IVec2::new((i % width) as i32, (i / width) as i32);
+            let wall = nearest[i];
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let n = cell + IVec2::new(dx, dy);
+                    if n.x < 0 || n.y < 0 || n.x >= width as i32 || n.y >= height as i32 {
+                        continue;
+                    }
+                    let j = n.y as usize * width + n.x as usize;
+                    let distance = (n - wall).as_vec2().length() * resolution;
+                    if distance < distances[j] && distance <= AMCL_LIKELIHOOD_MAX_DIST_PX {
+                        distances[j] = distance;
+                        nearest[j] = wall;
+                        queue.push_back(j);
+                    }
+                }
+            }
+        }
+
+        for distance in distances.iter_mut() {
+            *distance = distance.min(AMCL_LIKELIHOOD_MAX_DIST_PX);
+        }
+
+        Self {
+            resolution,
+            origin,
+            width,
+            height,
+            distances,
+            free_cells,
+        }
+    }
+
+    /// Distance from `pos` to the nearest wall (capped; off the map counts as far)
+    pub fn distance_at(&self, pos: Vec2) -> f32 {
+        let cell = ((pos - self.origin) / self.resolution).round();
+        if cell.x < 0.0
+            || cell.y < 0.0
+            || cell.x >= self.width as f32
+            || cell.y >= self.height as f32
+        {
+            return AMCL_LIKELIHOOD_MAX_DIST_PX;
+        }
+        self.distances[cell.y as usize * self.width + cell.x as usize]
+    }
+}
+
+/// Prepares the likelihood field once the level is up, from the `KnownMap`
+/// if one was supplied, otherwise from the level mask.
+pub fn build_likelihood_field_system(
+    mut commands: Commands,
+    level: Res<ActiveLevel>,
+    known_map: Option<Res<KnownMap>>,
+) {
+    let field = match known_map {
+        Some(known_map) => {
+            info!("[Localization] Localizing against the supplied map");
+            LikelihoodField::from_grid(&known_map.0)
+        }
+        None => LikelihoodField::from_mask(&level.mask, level.descriptor.spawn.position()),
+    };
+    commands.insert_resource(field);
+}
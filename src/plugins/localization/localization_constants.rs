@@ -0,0 +1,49 @@
+// ==========================
+// AMCL (Monte Carlo localization)
+// ==========================
+
+// Particle count
+pub const AMCL_PARTICLES: usize = 600;
+
+// Initial spread around the spawn pose
+pub const AMCL_INIT_SIGMA_PX: f32 = 10.0;
+pub const AMCL_INIT_SIGMA_YAW_DEG: f32 = 5.0;
+
+// Odometry motion model noise (as ROS amcl's `odom_alpha1..4`):
+// rotation from rotation (rad/rad), rotation from translation (rad/m),
+// translation from translation (m/m), translation from rotation (m/rad)
+pub const AMCL_ALPHA_ROT_ROT: f32 = 0.2;
+pub const AMCL_ALPHA_ROT_TRANS: f32 = 0.2;
+pub const AMCL_ALPHA_TRANS_TRANS: f32 = 0.2;
+pub const AMCL_ALPHA_TRANS_ROT: f32 = 0.05;
+
+// Likelihood-field sensor model: beams used per scan, std-dev of a hit around
+// the nearest wall, and the hit / random-return mixture
+pub const AMCL_BEAMS_PER_SCAN: usize = 45;
+pub const AMCL_SIGMA_HIT_PX: f32 = 12.0;
+pub const AMCL_Z_HIT: f32 = 0.9;
+pub const AMCL_Z_RAND: f32 = 0.1;
+// Distances to walls are only tracked this far out (the likelihood is flat beyond)
+pub const AMCL_LIKELIHOOD_MAX_DIST_PX: f32 = 48.0;
+
+// Only weigh a scan once the robot has moved this much since the last one
+// (standing still would otherwise collapse the particle set)
+pub const AMCL_UPDATE_MIN_TRANS_PX: f32 = 8.0;
+pub const AMCL_UPDATE_MIN_ROT_DEG: f32 = 5.0;
+
+// Resample when the effective particle count drops below this fraction
+pub const AMCL_RESAMPLE_NEFF_FRACTION: f32 = 0.5;
+
+// Recovery (augmented MCL): slow / fast averages of the scan likelihood; when
+// the fast one falls behind, random particles are injected across the map
+pub const AMCL_ALPHA_SLOW: f32 = 0.001;
+pub const AMCL_ALPHA_FAST: f32 = 0.1;
+// ...by this much: a handful of beams makes the per-scan fit noisy, and
+// scattering particles on every dip loses a good track
+pub const AMCL_RECOVERY_RATIO: f32 = 0.5;
+
+// Odometry poses kept to look up where the robot was when a scan started
+pub const AMCL_ODOM_HISTORY_SECS: f32 = 2.0;
+
+// After a kidnap, the hero counts as found again once the estimate is this close
+pub const AMCL_RECOVERED_ERROR_PX: f32 = 30.0;
@@ -0,0 +1,69 @@
+use super::{
+    amcl::{amcl_debug_draw_system, amcl_system, AmclFilter, AmclPose},
+    likelihood_field::{build_likelihood_field_system, LikelihoodField},
+};
+use crate::app::{RobotSet, SimMode};
+use crate::systems::level::level_ready;
+use crate::systems::robot::laser_scan::assemble_laser_scan_system;
+use bevy::prelude::*;
+
+// ┌────────────────────────────────────────────────────────────────────────────┐
+// │                          LOCALIZATION OVERVIEW                             │
+// └────────────────────────────────────────────────────────────────────────────┘
+//
+// Estimates where the robot is on a map it already has, from odometry and
+// LiDAR, instead of reading the simulator's ground truth.
+//
+// ▶ `LikelihoodField` (likelihood_field.rs)
+//    - Distance to the nearest wall for every cell of the known map: the
+//      `KnownMap` resource (a saved `OccupancyGrid`) if supplied, otherwise
+//      the level mask. Built once, when the level is ready.
+//
+// ▶ `amcl_system` (amcl.rs)
+//    - Monte Carlo localization, as ROS `amcl`: particles are moved with the
+//      odometry motion model and weighed per `LaserScan` with the
+//      likelihood-field model, then low-variance resampled.
+//    - Recovery: slow / fast averages of the scan likelihood; when the fast
+//      one drops (e.g. after a kidnap) random particles are injected.
+//    - Publishes `AmclPose` (pose + covariance) and, with
+//      `PoseSource::Odometry`, the map → odom correction in `TransformTree`.
+//
+// ▶ `amcl_debug_draw_system` (windowed)
+//    - Particles as short blue headings; estimate in yellow with a 2σ ellipse.
+//
+// Only robots carrying an `AmclFilter` are localized (`HeroSensors::localization`).
+
+/// Which localizer the hero carries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Localization {
+    #[default]
+    None,
+    /// Particle filter against the known map
+    Amcl,
+}
+
+pub struct LocalizationPlugin;
+
+impl Plugin for LocalizationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AmclPose>()
+            .add_systems(
+                FixedUpdate,
+                build_likelihood_field_system
+                    .before(RobotSet::Sense)
+                    .run_if(level_ready)
+                    .run_if(not(resource_exists::<LikelihoodField>))
+                    .run_if(any_with_component::<AmclFilter>),
+            )
+            .add_systems(
+                FixedUpdate,
+                amcl_system
+                    .after(assemble_laser_scan_system)
+                    .in_set(RobotSet::Sense),
+            );
+
+        if app.world.resource::<SimMode>().is_windowed() {
+            app.add_systems(Update, amcl_debug_draw_system);
+        }
+    }
+}
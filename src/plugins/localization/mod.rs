@@ -0,0 +1,4 @@
+pub mod amcl;
+pub mod likelihood_field;
+pub mod localization_constants;
+pub mod localization_plugin;
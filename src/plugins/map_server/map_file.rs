@@ -0,0 +1,398 @@
+use bevy::prelude::*;
+
+use super::map_server_constants::*;
+use crate::components::occupancy_grid::{CellState, OccupancyGrid};
+use crate::constants::METERS_PER_PIXEL;
+
+/// How pixel values map to occupancy (`mode:` in the YAML)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MapMode {
+    /// Occupied / free / unknown by the thresholds (the default)
+    #[default]
+    Trinary,
+    /// As trinary, with the values between the thresholds graded; Pick.e's
+    /// grid reads those as unknown
+    Scale,
+    /// Pixel values are occupancy percentages (0-100, anything else unknown)
+    Raw,
+}
+
+/// The YAML half of a ROS `map_server` map
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapMetadata {
+    /// Image file, relative to the YAML file
+    pub image: String,
+    /// Metres per pixel
+    pub resolution: f32,
+    /// Pose of the lower-left pixel in the map frame (m, m, rad)
+    pub origin: Vec3,
+    /// Whether white means occupied
+    pub negate: bool,
+    pub occupied_thresh: f32,
+    pub free_thresh: f32,
+    pub mode: MapMode,
+}
+
+impl MapMetadata {
+    /// Reads the flat `key: value` YAML `map_server` loads
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut image = None;
+        let mut resolution = None;
+        let mut origin = None;
+        let mut metadata = Self {
+            image: String::new(),
+            resolution: 0.0,
+            origin: Vec3::ZERO,
+            negate: false,
+            occupied_thresh: MAP_OCCUPIED_THRESH,
+            free_thresh: MAP_FREE_THRESH,
+            mode: MapMode::Trinary,
+        };
+
+        for line in text.lines() {
+            let line = line.split_once('#').map_or(line, |(before, _)| before);
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            match key.trim() {
+                "image" => image = Some(value.to_string()),
+                "resolution" => resolution = value.parse().ok(),
+                "origin" => {
+                    let parts: Option<Vec<f32>> = value
+                        .trim_start_matches('[')
+                        .trim_end_matches(']')
+                        .split(',')
+                        .map(|part| part.trim().parse().ok())
+                        .collect();
+                    origin = parts
+                        .filter(|parts| parts.len() == 3)
+                        .map(|p| Vec3::from_slice(&p));
+                }
+                "negate" => metadata.negate = value == "1" || value == "true",
+                "occupied_thresh" => metadata.occupied_thresh = value.parse().ok()?,
+                "free_thresh" => metadata.free_thresh = value.parse().ok()?,
+                "mode" => {
+                    metadata.mode = match value {
+                        "trinary" => MapMode::Trinary,
+                        "scale" => MapMode::Scale,
+                        "raw" => MapMode::Raw,
+                        _ => {
+                            warn!("[Map] Unknown map mode '{value}'");
+                            return None;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let (Some(image), Some(resolution), Some(origin)) = (image, resolution, origin) else {
+            warn!("[Map] Map YAML needs image, resolution and origin");
+            return None;
+        };
+        Some(Self {
+            image,
+            resolution,
+            origin,
+            ..metadata
+        })
+    }
+
+    /// As `map_saver` writes it
+    pub fn format(&self) -> String {
+        let mut text = format!(
+            "image: {}\nresolution: {:.6}\norigin: [{:.6}, {:.6}, {:.6}]\nnegate: {}\noccupied_thresh: {}\nfree_thresh: {}\n",
+            self.image,
+            self.resolution,
+            self.origin.x,
+            self.origin.y,
+            self.origin.z,
+            u8::from(self.negate),
+            self.occupied_thresh,
+            self.free_thresh,
+        );
+        match self.mode {
+            MapMode::Trinary => {}
+            MapMode::Scale => text.push_str("mode: scale\n"),
+            MapMode::Raw => text.push_str("mode: raw\n"),
+        }
+        text
+    }
+}
+
+/// The grid's known cells as a `map_server` map: a trinary PGM image
+/// covering them (walls black, free space near-white, unknown grey) and
+/// its metadata, pointing at `image_name`. `None` if nothing is known yet.
+pub fn format_map(grid: &OccupancyGrid, image_name: &str) -> Option<(MapMetadata, Vec<u8>)> {
+    let bounds = grid
+        .cells()
+        .filter(|(_, log_odds)| grid.model.state(*log_odds) != CellState::Unknown)
+        .map(|(cell, _)| IRect::from_corners(cell, cell))
+        .reduce(|bounds, cell| bounds.union(cell))?;
+    let (width, height) = (bounds.width() + 1, bounds.height() + 1);
+
+    let metres_per_cell = grid.resolution * METERS_PER_PIXEL;
+    let mut pgm =
+        format!("P5\n# CREATOR: pick-e {metres_per_cell:.3} m/pix\n{width} {height}\n255\n")
+            .into_bytes();
+    // Image rows run top-down
+    for y in (bounds.min.y..=bounds.max.y).rev() {
+        for x in bounds.min.x..=bounds.max.x {
+            pgm.push(match grid.state_at(IVec2::new(x, y)) {
+                CellState::Solid => MAP_PIXEL_OCCUPIED,
+                CellState::Free => MAP_PIXEL_FREE,
+                CellState::Unknown => MAP_PIXEL_UNKNOWN,
+            });
+        }
+    }
+
+    let corner = grid.origin + bounds.min.as_vec2() * grid.resolution;
+    let metadata = MapMetadata {
+        image: image_name.to_string(),
+        resolution: metres_per_cell,
+        origin: (corner * METERS_PER_PIXEL).extend(0.0),
+        negate: false,
+        occupied_thresh: MAP_OCCUPIED_THRESH,
+        free_thresh: MAP_FREE_THRESH,
+        mode: MapMode::Trinary,
+    };
+    Some((metadata, pgm))
+}
+
+/// An occupancy grid from a `map_server` map (`pgm` is the image the
+/// metadata names, binary or ASCII PGM). Cells are classified exactly as
+/// `map_server` does, by the thresholds in the metadata. The origin's yaw
+/// is not supported.
+pub fn parse_map(metadata: &MapMetadata, pgm: &[u8]) -> Option<OccupancyGrid> {
+    let Some((width, height, max_value, pixels)) = parse_pgm(pgm) else {
+        warn!("[Map] {} is not a PGM image", metadata.image);
+        return None;
+    };
+    if metadata.resolution <= 0.0 {
+        warn!("[Map] Bad map resolution {}", metadata.resolution);
+        return None;
+    }
+    if metadata.origin.z != 0.0 {
+        warn!(
+            "[Map] Ignoring the map origin's yaw ({} rad)",
+            metadata.origin.z
+        );
+    }
+
+    let mut grid = OccupancyGrid::new(
+        metadata.resolution / METERS_PER_PIXEL,
+        metadata.origin.truncate() / METERS_PER_PIXEL,
+    );
+    for (i, value) in pixels.iter().enumerate() {
+        let value = *value as f32;
+        let occupancy = match metadata.mode {
+            MapMode::Raw if value <= 100.0 => value / 100.0,
+            MapMode::Raw => continue,
+            _ if metadata.negate => value / max_value,
+            _ => (max_value - value) / max_value,
+        };
+        let state = if occupancy > metadata.occupied_thresh {
+            CellState::Solid
+        } else if occupancy < metadata.free_thresh {
+            CellState::Free
+        } else {
+            continue;
+        };
+        // Image rows run top-down
+        let cell = IVec2::new((i % width) as i32, (height - 1 - i / width) as i32);
+        grid.set_cell(cell, state);
+    }
+    Some(grid)
+}
+
+/// `(width, height, max value, pixels)` of a binary (P5) or ASCII (P2) PGM
+fn parse_pgm(bytes: &[u8]) -> Option<(usize, usize, f32, Vec<u16>)> {
+    // Header: magic, width, height, max value; whitespace-separated, with
+    // `#` comments, then a single whitespace byte before binary data
+    let mut header = Vec::new();
+    let mut pos = 0;
+    while header.len() < 4 {
+        while bytes.get(pos)?.is_ascii_whitespace() {
+            pos += 1;
+        }
+        if bytes[pos] == b'#' {
+            while *bytes.get(pos)? != b'\n' {
+                pos += 1;
+            }
+            continue;
+        }
+        let start = pos;
+        while !bytes.get(pos)?.is_ascii_whitespace() {
+            pos += 1;
+        }
+        header.push(std::str::from_utf8(&bytes[start..pos]).ok()?);
+    }
+    let width: usize = header[1].parse().ok()?;
+    let height: usize = header[2].parse().ok()?;
+    let max_value: u16 = header[3].parse().ok().filter(|max| *max > 0)?;
+    let count = width.checked_mul(height).filter(|count| *count > 0)?;
+    if count > MAP_MAX_CELLS {
+        warn!("[Map] A {width}x{height} image is too large to load");
+        return None;
+    }
+
+    let pixels: Vec<u16> = match header[0] {
+        "P5" => {
+            let data = bytes.get(pos + 1..)?;
+            if max_value < 256 {
+                data.get(..count)?.iter().map(|v| *v as u16).collect()
+            } else {
+                data.get(..count.checked_mul(2)?)?
+                    .chunks_exact(2)
+                    .map(|v| u16::from_be_bytes([v[0], v[1]]))
+                    .collect()
+            }
+        }
+        "P2" => std::str::from_utf8(&bytes[pos..])
+            .ok()?
+            .split_whitespace()
+            .take(count)
+            .map(|v| v.parse().ok())
+            .collect::<Option<Vec<u16>>>()
+            .filter(|pixels| pixels.len() == count)?,
+        _ => return None,
+    };
+    Some((width, height, max_value as f32, pixels))
+}
+
+/// Writes `<dir>/<name>.yaml` and `<name>.pgm`; returns the YAML path
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_map(grid: &OccupancyGrid, dir: &str, name: &str) -> Option<String> {
+    let Some((metadata, pgm)) = format_map(grid, &format!("{name}.pgm")) else {
+        warn!("[Map] Nothing mapped yet; not saving");
+        return None;
+    };
+    let yaml_path = format!("{dir}/{name}.yaml");
+    let written = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(format!("{dir}/{}", metadata.image), pgm))
+        .and_then(|_| std::fs::write(&yaml_path, metadata.format()));
+    if let Err(e) = written {
+        warn!("[Map] Failed to save the map: {e}");
+        return None;
+    }
+    Some(yaml_path)
+}
+
+/// Reads a map from its YAML file (the image is looked up beside it)
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_map(yaml_path: &str) -> Option<OccupancyGrid> {
+    let text = std::fs::read_to_string(yaml_path)
+        .map_err(|e| warn!("[Map] Failed to read {yaml_path}: {e}"))
+        .ok()?;
+    let metadata = MapMetadata::parse(&text)?;
+    let image_path = std::path::Path::new(yaml_path)
+        .parent()
+        .unwrap_or(std::path::Path::new(""))
+        .join(&metadata.image);
+    let pgm = std::fs::read(&image_path)
+        .map_err(|e| warn!("[Map] Failed to read {}: {e}", image_path.display()))
+        .ok()?;
+    parse_map(&metadata, &pgm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(yaml: &str) -> MapMetadata {
+        MapMetadata::parse(yaml).expect("valid map YAML")
+    }
+
+    #[test]
+    fn grid_round_trips_through_pgm_and_yaml() {
+        let mut grid = OccupancyGrid::new(10.0, Vec2::ZERO);
+        grid.set_cell(IVec2::new(-3, -2), CellState::Solid);
+        grid.set_cell(IVec2::new(-2, -2), CellState::Free);
+        grid.set_cell(IVec2::new(0, 0), CellState::Free);
+        grid.set_cell(IVec2::new(4, 1), CellState::Solid);
+
+        let (saved, pgm) = format_map(&grid, "map.pgm").unwrap();
+        let (width, height, max_value, pixels) = parse_pgm(&pgm).unwrap();
+        assert_eq!((width, height, max_value), (8, 4, 255.0));
+        // Top row first: (4, 1) ends it, (-3, -2) starts the bottom one
+        assert_eq!(pixels[7], MAP_PIXEL_OCCUPIED as u16);
+        assert_eq!(pixels[24], MAP_PIXEL_OCCUPIED as u16);
+        assert_eq!(pixels[25], MAP_PIXEL_FREE as u16);
+        assert_eq!(pixels[0], MAP_PIXEL_UNKNOWN as u16);
+
+        let loaded_metadata = metadata(&saved.format());
+        assert_eq!(loaded_metadata.image, "map.pgm");
+        assert!((loaded_metadata.resolution - saved.resolution).abs() < 1e-6);
+        assert!(loaded_metadata.origin.abs_diff_eq(saved.origin, 1e-5));
+        assert_eq!(loaded_metadata.mode, MapMode::Trinary);
+
+        let loaded = parse_map(&loaded_metadata, &pgm).unwrap();
+        for y in -2..=1 {
+            for x in -3..=4 {
+                let cell = IVec2::new(x, y);
+                let loaded_cell = loaded.world_to_cell(grid.cell_to_world(cell)).unwrap();
+                assert_eq!(grid.state_at(cell), loaded.state_at(loaded_cell), "{cell}");
+            }
+        }
+    }
+
+    #[test]
+    fn metadata_reads_map_server_yaml() {
+        let parsed = metadata(
+            "image: testmap.pgm\n\
+             resolution: 0.050000\n\
+             origin: [-12.2, -8.5, 0.0]  # lower-left pixel\n\
+             negate: 0\n\
+             occupied_thresh: 0.65\n\
+             free_thresh: 0.196\n\
+             mode: scale\n",
+        );
+        assert_eq!(parsed.image, "testmap.pgm");
+        assert_eq!(parsed.resolution, 0.05);
+        assert_eq!(parsed.origin, Vec3::new(-12.2, -8.5, 0.0));
+        assert!(!parsed.negate);
+        assert_eq!(parsed.mode, MapMode::Scale);
+        assert_eq!(metadata(&parsed.format()), parsed);
+
+        assert!(MapMetadata::parse("image: map.pgm\nresolution: 0.05\n").is_none());
+    }
+
+    #[test]
+    fn ascii_pgm_with_comments_is_read() {
+        let pgm = b"P2\n# CREATOR: test\n3 2\n255\n0 254 205\n205 0 254\n";
+        assert_eq!(
+            parse_pgm(pgm),
+            Some((3, 2, 255.0, vec![0, 254, 205, 205, 0, 254]))
+        );
+    }
+
+    #[test]
+    fn sixteen_bit_pgm_is_classified_against_its_max_value() {
+        let mut pgm = b"P5\n2 1\n65535\n".to_vec();
+        pgm.extend_from_slice(&[0x00, 0x00, 0xff, 0xfe]);
+        assert_eq!(parse_pgm(&pgm), Some((2, 1, 65535.0, vec![0, 65534])));
+
+        let grid = parse_map(
+            &metadata("image: map.pgm\nresolution: 0.05\norigin: [0, 0, 0]\n"),
+            &pgm,
+        )
+        .unwrap();
+        assert_eq!(grid.state_at(IVec2::new(0, 0)), CellState::Solid);
+        assert_eq!(grid.state_at(IVec2::new(1, 0)), CellState::Free);
+    }
+
+    #[test]
+    fn malformed_pgm_is_rejected() {
+        // Overflowing, empty and oversized images
+        assert!(parse_pgm(b"P5 4294967296 4294967296 255\n").is_none());
+        assert!(parse_pgm(b"P5\n0 4\n255\n").is_none());
+        assert!(parse_pgm(b"P5\n5000 5000\n255\n").is_none());
+        // Truncated data or header, wrong magic, zero max value
+        assert!(parse_pgm(b"P5\n4 4\n255\n\x00\x00\x00").is_none());
+        assert!(parse_pgm(b"P5\n2 2\n").is_none());
+        assert!(parse_pgm(b"P6\n1 1\n255\n\x00").is_none());
+        assert!(parse_pgm(b"P5\n1 1\n0\n\x00").is_none());
+        assert!(parse_pgm(b"P2\n2 1\n255\n0 x\n").is_none());
+    }
+}
@@ -0,0 +1,24 @@
+// ==========================
+// Map files (ROS map_server)
+// ==========================
+
+// Where native runs save and load the map: `<dir>/<name>.yaml` + `<name>.pgm`
+// (also the file names offered for download on the web)
+#[cfg(not(target_arch = "wasm32"))]
+pub const MAP_SAVE_DIR: &str = "maps";
+pub const MAP_FILE_NAME: &str = "pick-e-map";
+
+// Thresholds written to the YAML, as `map_saver` writes them: a pixel whose
+// occupancy probability (255 - value) / 255 is above OCCUPIED is a wall,
+// below FREE is free space, anything between is unknown
+pub const MAP_OCCUPIED_THRESH: f32 = 0.65;
+pub const MAP_FREE_THRESH: f32 = 0.196;
+
+// Largest map accepted, in image pixels and in grid cells once resampled
+// onto the hero's grid (a corrupt or hostile header must not exhaust memory)
+pub const MAP_MAX_CELLS: usize = 4096 * 4096;
+
+// Pixel values of a saved (trinary) map
+pub const MAP_PIXEL_OCCUPIED: u8 = 0;
+pub const MAP_PIXEL_FREE: u8 = 254;
+pub const MAP_PIXEL_UNKNOWN: u8 = 205;
@@ -0,0 +1,228 @@
+#[cfg(not(target_arch = "wasm32"))]
+use super::map_file::{load_map, save_map};
+use super::map_server_constants::*;
+#[cfg(target_arch = "wasm32")]
+use super::{
+    map_file::{format_map, parse_map, MapMetadata},
+    web,
+};
+use crate::app::{RobotSet, SimMode};
+use crate::bundles::hero::HeroController;
+use crate::components::occupancy_grid::{CellState, OccupancyGrid};
+use crate::plugins::localization::likelihood_field::{KnownMap, LikelihoodField};
+use bevy::prelude::*;
+
+// ┌────────────────────────────────────────────────────────────────────────────┐
+// │                             MAP SERVER OVERVIEW                            │
+// └────────────────────────────────────────────────────────────────────────────┘
+//
+// Saves and loads the hero's `OccupancyGrid` in ROS `map_server` format (a
+// PGM image plus YAML metadata), so Pick.e's maps can be compared with maps
+// from real robots and a run can start from an earlier map.
+//
+// ▶ map_file.rs
+//    - `format_map` / `parse_map`: grid ↔ trinary PGM + `MapMetadata`
+//      (resolution and origin in metres, occupied / free thresholds), as
+//      `map_saver` writes and `map_server` reads them.
+//    - `save_map` / `load_map` (native): files under `maps/`.
+//
+// ▶ `map_keys_system` (windowed)
+//    - O saves the map, L loads one: files on native; on the web, a download
+//      of both files and an upload picker (select the .yaml and .pgm).
+//
+// ▶ `load_map_system`
+//    - A loaded map replaces the hero's grid and becomes the `KnownMap`, so
+//      AMCL re-localizes against it.
+//
+// ▶ `seed_grid_from_known_map_system`
+//    - A run given a `KnownMap` up front (`--map`) starts its grid from it.
+//      (A pose graph re-renders the grid from its keyframes, dropping it.)
+
+/// Save the hero's map (to `maps/` on native, as a download on the web)
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct SaveMapRequest;
+
+/// Load a map and carry on from it (from `maps/` on native, an upload on the web)
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct LoadMapRequest;
+
+pub struct MapServerPlugin;
+
+impl Plugin for MapServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveMapRequest>()
+            .add_event::<LoadMapRequest>()
+            .add_systems(Update, (save_map_system, load_map_system))
+            .add_systems(
+                FixedUpdate,
+                seed_grid_from_known_map_system.before(RobotSet::Sense),
+            );
+
+        if app.world.resource::<SimMode>().is_windowed() {
+            app.add_systems(Update, map_keys_system.before(save_map_system));
+        }
+    }
+}
+
+/// O saves the map, L loads one (windowed)
+pub fn map_keys_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut saves: EventWriter<SaveMapRequest>,
+    mut loads: EventWriter<LoadMapRequest>,
+) {
+    if keys.just_pressed(KeyCode::KeyO) {
+        saves.send(SaveMapRequest);
+    }
+    if keys.just_pressed(KeyCode::KeyL) {
+        loads.send(LoadMapRequest);
+    }
+}
+
+pub fn save_map_system(
+    mut requests: EventReader<SaveMapRequest>,
+    grids: Query<&OccupancyGrid, With<HeroController>>,
+) {
+    if requests.read().count() == 0 {
+        return;
+    }
+    let Ok(grid) = grids.get_single() else {
+        return;
+    };
+
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(path) = save_map(grid, MAP_SAVE_DIR, MAP_FILE_NAME) {
+        info!("[Map] Saved {path}");
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let image = format!("{MAP_FILE_NAME}.pgm");
+        match format_map(grid, &image) {
+            Some((metadata, pgm)) => {
+                web::download(&image, &pgm);
+                web::download(
+                    &format!("{MAP_FILE_NAME}.yaml"),
+                    metadata.format().as_bytes(),
+                );
+            }
+            None => warn!("[Map] Nothing mapped yet; not saving"),
+        }
+    }
+}
+
+/// Loads a map on request (on the web the picked files arrive frames later)
+/// and puts it in play: the hero's grid is replaced and it becomes the
+/// `KnownMap` (the likelihood field is rebuilt from it)
+pub fn load_map_system(
+    mut commands: Commands,
+    mut requests: EventReader<LoadMapRequest>,
+    mut grids: Query<&mut OccupancyGrid, With<HeroController>>,
+) {
+    let requested = requests.read().count() > 0;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let loaded = requested
+        .then(|| load_map(&format!("{MAP_SAVE_DIR}/{MAP_FILE_NAME}.yaml")))
+        .flatten();
+    #[cfg(target_arch = "wasm32")]
+    let loaded = {
+        if requested {
+            web::open_upload();
+        }
+        web::take_upload().and_then(|files| map_from_files(&files))
+    };
+
+    let Some(map) = loaded else {
+        return;
+    };
+    for mut grid in grids.iter_mut() {
+        let Some(resampled) = resample(&map, &grid) else {
+            return;
+        };
+        replace_grid(&mut grid, resampled);
+    }
+    info!("[Map] Loaded a map ({} chunks)", map.chunk_count());
+    commands.remove_resource::<LikelihoodField>();
+    commands.insert_resource(KnownMap(map));
+}
+
+/// Starts the hero's grid from the `KnownMap` a run was given
+pub fn seed_grid_from_known_map_system(
+    known_map: Option<Res<KnownMap>>,
+    mut grids: Query<&mut OccupancyGrid, (With<HeroController>, Added<OccupancyGrid>)>,
+) {
+    let Some(known_map) = known_map else {
+        return;
+    };
+    for mut grid in grids.iter_mut() {
+        if let Some(resampled) = resample(&known_map.0, &grid) {
+            replace_grid(&mut grid, resampled);
+        }
+    }
+}
+
+/// Replaces the grid's contents with `map`, reporting both the old and new
+/// extent as changed so the renderer and planner revisit them
+fn replace_grid(grid: &mut OccupancyGrid, map: OccupancyGrid) {
+    let old_bounds = grid.bounds();
+    *grid = map;
+    for bounds in [old_bounds, grid.bounds()].into_iter().flatten() {
+        grid.mark_dirty(bounds);
+    }
+}
+
+/// `map` on `like`'s cells (same origin, resolution and model): a map from
+/// elsewhere has its own, and the planner's cells must keep their meaning.
+/// `None` if it would span more than `MAP_MAX_CELLS` of them.
+fn resample(map: &OccupancyGrid, like: &OccupancyGrid) -> Option<OccupancyGrid> {
+    let mut grid = OccupancyGrid::new(like.resolution, like.origin).with_model(like.model);
+    let Some(bounds) = map.bounds() else {
+        return Some(grid);
+    };
+    let corner = |cell: IVec2| map.origin + cell.as_vec2() * map.resolution;
+    let (Some(min), Some(max)) = (
+        grid.world_to_cell(corner(bounds.min)),
+        grid.world_to_cell(corner(bounds.max + IVec2::ONE)),
+    ) else {
+        return Some(grid);
+    };
+    let (width, height) = (
+        max.x as i64 - min.x as i64 + 1,
+        max.y as i64 - min.y as i64 + 1,
+    );
+    if width.saturating_mul(height) > MAP_MAX_CELLS as i64 {
+        warn!("[Map] The map would span {width}x{height} cells; not loading it");
+        return None;
+    }
+
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let cell = IVec2::new(x, y);
+            let state = map
+                .world_to_cell(grid.cell_to_world(cell))
+                .map(|map_cell| map.state_at(map_cell));
+            if let Some(state @ (CellState::Solid | CellState::Free)) = state {
+                grid.set_cell(cell, state);
+            }
+        }
+    }
+    Some(grid)
+}
+
+/// The map among uploaded files: the YAML and the image it names
+#[cfg(target_arch = "wasm32")]
+fn map_from_files(files: &[(String, Vec<u8>)]) -> Option<OccupancyGrid> {
+    let Some((_, yaml)) = files
+        .iter()
+        .find(|(name, _)| name.ends_with(".yaml") || name.ends_with(".yml"))
+    else {
+        warn!("[Map] Pick the map's .yaml and .pgm files together");
+        return None;
+    };
+    let metadata = MapMetadata::parse(std::str::from_utf8(yaml).ok()?)?;
+    let image_name = metadata.image.rsplit('/').next().unwrap_or_default();
+    let Some((_, pgm)) = files.iter().find(|(name, _)| name == image_name) else {
+        warn!("[Map] {image_name} was not among the picked files");
+        return None;
+    };
+    parse_map(&metadata, pgm)
+}
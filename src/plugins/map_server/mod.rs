@@ -0,0 +1,5 @@
+pub mod map_file;
+pub mod map_server_constants;
+pub mod map_server_plugin;
+#[cfg(target_arch = "wasm32")]
+mod web;
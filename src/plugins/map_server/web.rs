@@ -0,0 +1,78 @@
+use wasm_bindgen::prelude::*;
+
+// Browser side of saving / loading maps: downloads go through a temporary
+// link, uploads through a file picker whose files are held until polled.
+#[wasm_bindgen(inline_js = r#"
+let pending = null;
+
+export function pick_e_download(name, bytes) {
+    const url = URL.createObjectURL(new Blob([bytes.slice()]));
+    const link = document.createElement("a");
+    link.href = url;
+    link.download = name;
+    link.click();
+    setTimeout(() => URL.revokeObjectURL(url), 0);
+}
+
+export function pick_e_open_upload() {
+    const input = document.createElement("input");
+    input.type = "file";
+    input.multiple = true;
+    input.accept = ".yaml,.yml,.pgm";
+    input.onchange = async () => {
+        const files = [];
+        for (const file of input.files) {
+            files.push({ name: file.name, bytes: new Uint8Array(await file.arrayBuffer()) });
+        }
+        pending = files;
+    };
+    input.click();
+}
+
+export function pick_e_upload_count() {
+    return pending === null ? 0 : pending.length;
+}
+
+export function pick_e_upload_name(i) {
+    return pending[i].name;
+}
+
+export function pick_e_upload_bytes(i) {
+    return pending[i].bytes;
+}
+
+export function pick_e_clear_upload() {
+    pending = null;
+}
+"#)]
+extern "C" {
+    fn pick_e_download(name: &str, bytes: &[u8]);
+    fn pick_e_open_upload();
+    fn pick_e_upload_count() -> u32;
+    fn pick_e_upload_name(i: u32) -> String;
+    fn pick_e_upload_bytes(i: u32) -> Vec<u8>;
+    fn pick_e_clear_upload();
+}
+
+/// Offers `bytes` to the user as a file download
+pub fn download(name: &str, bytes: &[u8]) {
+    pick_e_download(name, bytes);
+}
+
+/// Opens the browser's file picker (files arrive later, see `take_upload`)
+pub fn open_upload() {
+    pick_e_open_upload();
+}
+
+/// The files picked since the last call, as `(name, contents)`
+pub fn take_upload() -> Option<Vec<(String, Vec<u8>)>> {
+    let count = pick_e_upload_count();
+    if count == 0 {
+        return None;
+    }
+    let files = (0..count)
+        .map(|i| (pick_e_upload_name(i), pick_e_upload_bytes(i)))
+        .collect();
+    pick_e_clear_upload();
+    Some(files)
+}
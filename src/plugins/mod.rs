@@ -1 +1,5 @@
 pub mod auto_nav;
+pub mod localization;
+pub mod map_server;
+pub mod sim;
+pub mod slam;
@@ -0,0 +1,84 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::bundles::hero::HeroController;
+use crate::components::occupancy_grid::{CellState, OccupancyGrid};
+use crate::systems::level::{ActiveLevel, LevelMask};
+
+/// Exploration coverage: what the hero has mapped as free vs. the free space it
+/// could actually reach (flood-filled from the spawn over the level mask).
+#[derive(Resource, Default, Debug, Clone)]
+pub struct CoverageStats {
+    /// Hero-grid cells that are (mostly) reachable free space in the level mask
+    pub reachable_cells: usize,
+    /// Of those, cells the hero has mapped as `Free`
+    pub explored_cells: usize,
+    pub explored_pct: f32,
+}
+
+/// Recounts coverage against the hero's occupancy grid.
+///
+/// The ground truth is built once, on the first run after the level is ready,
+/// in the grid's own cell frame so each frame is just a lookup per cell.
+pub fn update_coverage_system(
+    level: Res<ActiveLevel>,
+    mut coverage: ResMut<CoverageStats>,
+    mut truth: Local<Option<Vec<IVec2>>>,
+    grids: Query<&OccupancyGrid, With<HeroController>>,
+) {
+    let Ok(grid) = grids.get_single() else {
+        return;
+    };
+
+    if truth.is_none() {
+        let cells = reachable_grid_cells(&level.mask, level.descriptor.spawn.position(), grid);
+        coverage.reachable_cells = cells.len();
+        *truth = Some(cells);
+    }
+    let Some(cells) = truth.as_ref() else {
+        return;
+    };
+
+    coverage.explored_cells = cells
+        .iter()
+        .filter(|c| grid.state_at(**c) == CellState::Free)
+        .count();
+    coverage.explored_pct = if cells.is_empty() {
+        0.0
+    } else {
+        100.0 * coverage.explored_cells as f32 / cells.len() as f32
+    };
+}
+
+/// Grid cells where at least half of the covered mask tiles are reachable from the spawn
+fn reachable_grid_cells(mask: &LevelMask, spawn: Vec2, grid: &OccupancyGrid) -> Vec<IVec2> {
+    let Some(start) = mask.find_tile_near(spawn) else {
+        warn!("[Coverage] Could not locate player start tile");
+        return Vec::new();
+    };
+    let reachable = mask.reachable_from(start);
+
+    // (reachable tiles, all tiles) per grid cell
+    let mut counts: HashMap<IVec2, (usize, usize)> = HashMap::new();
+    for (y, row) in reachable.iter().enumerate() {
+        for (x, is_reachable) in row.iter().enumerate() {
+            let Some(cell) = grid.world_to_cell(mask.tile_to_world(x, y)) else {
+                continue;
+            };
+            let entry = counts.entry(cell).or_default();
+            entry.1 += 1;
+            if *is_reachable {
+                entry.0 += 1;
+            }
+        }
+    }
+
+    let mut cells: Vec<IVec2> = counts
+        .into_iter()
+        .filter(|(_, (hit, all))| hit * 2 >= *all && *hit > 0)
+        .map(|(cell, _)| cell)
+        .collect();
+    cells.sort_by_key(|c| (c.y, c.x));
+    cells
+}
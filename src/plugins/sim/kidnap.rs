@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use super::sim_constants::*;
+use super::sim_metrics::SimMetrics;
+use super::sim_seed::SimSeed;
+use crate::bundles::hero::HeroController;
+use crate::components::frames::Pose2d;
+use crate::systems::level::{ActiveLevel, LevelMask};
+
+/// Ask for the hero to be picked up and dropped somewhere else (the
+/// kidnapped-robot test for localization)
+#[derive(Event, Debug, Clone, Copy, Default)]
+pub struct KidnapRequest;
+
+/// The hero was teleported: its wheels never turned, so odometry must not
+/// count the jump
+#[derive(Event, Debug, Clone, Copy)]
+pub struct Kidnapped {
+    pub entity: Entity,
+    pub from: Pose2d,
+    pub to: Pose2d,
+}
+
+/// K kidnaps the hero (windowed)
+pub fn kidnap_keys_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut requests: EventWriter<KidnapRequest>,
+) {
+    if keys.just_pressed(KeyCode::KeyK) {
+        requests.send(KidnapRequest);
+    }
+}
+
+/// Teleports the hero to a random reachable spot with a random heading.
+///
+/// Spots come from the seeded "kidnap" stream, so kidnap tests stay reproducible.
+pub fn kidnap_system(
+    mut requests: EventReader<KidnapRequest>,
+    mut kidnapped: EventWriter<Kidnapped>,
+    level: Res<ActiveLevel>,
+    seed: Res<SimSeed>,
+    mut rng: Local<Option<StdRng>>,
+    mut metrics: ResMut<SimMetrics>,
+    mut heroes: Query<
+        (Entity, &GlobalTransform, &mut Transform, &mut Velocity),
+        With<HeroController>,
+    >,
+) {
+    if requests.read().count() == 0 {
+        return;
+    }
+    let rng = rng.get_or_insert_with(|| seed.rng("kidnap"));
+
+    for (entity, global, mut transform, mut velocity) in heroes.iter_mut() {
+        let from = Pose2d::from_global(global);
+        let Some(spot) = pick_drop_spot(
+            &level.mask,
+            level.descriptor.spawn.position(),
+            from.translation,
+            rng,
+        ) else {
+            warn!("[Sim] Nowhere to drop the kidnapped hero — ignoring");
+            continue;
+        };
+
+        let to = Pose2d::new(
+            spot,
+            rng.gen_range(-std::f32::consts::PI..std::f32::consts::PI),
+        );
+        transform.translation = to.translation.extend(transform.translation.z);
+        transform.rotation = Quat::from_rotation_z(to.yaw);
+        *velocity = Velocity::zero();
+
+        info!(
+            "[Sim] Kidnapped hero from {:?} to {:?}",
+            from.translation, to.translation
+        );
+        metrics.kidnaps += 1;
+        metrics.kidnapped_at_secs = Some(metrics.elapsed_secs);
+        metrics.kidnap_recovery_secs = None;
+        kidnapped.send(Kidnapped { entity, from, to });
+    }
+}
+
+/// Give up looking for a drop spot after this many random picks
+const MAX_DROP_ATTEMPTS: usize = 1000;
+
+/// Centre of a random reachable tile far enough from `avoid`, with
+/// `SIM_KIDNAP_CLEARANCE_PX` of unblocked tiles around it
+fn pick_drop_spot(mask: &LevelMask, spawn: Vec2, avoid: Vec2, rng: &mut StdRng) -> Option<Vec2> {
+    let start = mask.find_tile_near(spawn)?;
+    let reachable = mask.reachable_from(start);
+    let candidates: Vec<(usize, usize)> = reachable
+        .iter()
+        .enumerate()
+        .flat_map(|(y, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, is_reachable)| **is_reachable)
+                .map(move |(x, _)| (x, y))
+        })
+        .filter(|&(x, y)| mask.tile_to_world(x, y).distance(avoid) >= SIM_KIDNAP_MIN_DISTANCE_PX)
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+
+    let reach = (SIM_KIDNAP_CLEARANCE_PX / mask.tile_size).ceil() as isize;
+    let clear = |x: usize, y: usize| {
+        (-reach..=reach).all(|dy| {
+            (-reach..=reach).all(|dx| {
+                let (nx, ny) = (x as isize + dx, y as isize + dy);
+                nx >= 0
+                    && ny >= 0
+                    && (nx as usize) < mask.width
+                    && (ny as usize) < mask.height
+                    && !mask.is_blocked(nx as usize, ny as usize)
+            })
+        })
+    };
+
+    (0..MAX_DROP_ATTEMPTS)
+        .map(|_| candidates[rng.gen_range(0..candidates.len())])
+        .find(|&(x, y)| clear(x, y))
+        .map(|(x, y)| mask.tile_to_world(x, y))
+}
@@ -0,0 +1,7 @@
+pub mod coverage;
+pub mod kidnap;
+pub mod sim_constants;
+pub mod sim_metrics;
+pub mod sim_plugin;
+pub mod sim_seed;
+pub mod sim_time;
@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+// Default fixed simulation rate (sensing, mapping, planning, driving and Rapier)
+pub const SIM_RATE_HZ: f64 = 60.0;
+
+// Time-scale presets
+pub const SIM_FAST_FORWARD_SCALE: f32 = 10.0;
+
+// "As fast as possible": grow the number of fixed steps per frame while frames
+// stay under this wall-clock budget, shrink it when they go over.
+pub const SIM_MAX_SPEED_FRAME_BUDGET: Duration = Duration::from_millis(33);
+pub const SIM_MAX_SPEED_MAX_STEPS: f32 = 2000.0;
+
+// Bevy's default cap on virtual time per frame (restored when leaving Max speed)
+pub const SIM_DEFAULT_MAX_DELTA: Duration = Duration::from_millis(250);
+
+// Stall detection: the hero counts as stuck once it has stayed within
+// SIM_STUCK_RADIUS_PX of the same spot for longer than SIM_STUCK_WINDOW_SECS.
+pub const SIM_STUCK_RADIUS_PX: f32 = 12.0;
+pub const SIM_STUCK_WINDOW_SECS: f32 = 3.0;
+
+// "Rotating in place": turning faster than this while barely translating
+pub const SIM_ROTATE_MIN_ANGVEL: f32 = 0.2; // rad/s
+pub const SIM_ROTATE_MAX_LINVEL: f32 = 5.0; // px/s
+
+// Kidnapping: the hero is dropped at least this far from where it was, on a
+// reachable spot with this much clearance from anything blocked
+pub const SIM_KIDNAP_MIN_DISTANCE_PX: f32 = 300.0;
+pub const SIM_KIDNAP_CLEARANCE_PX: f32 = 48.0;
@@ -0,0 +1,236 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use bevy_rapier2d::prelude::*;
+
+use crate::bundles::hero::HeroController;
+use crate::components::collectible::{Collectible, CollectionStats};
+use crate::components::frames::{Pose2d, TransformTree};
+use crate::plugins::auto_nav::plan_frontier_path_system::PathPlan;
+use crate::plugins::sim::sim_constants::*;
+use crate::plugins::slam::ekf_slam::EkfSlam;
+use crate::systems::level::MergedWall;
+
+/// Per-episode measurements, counted from the moment the level is ready.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct SimMetrics {
+    /// Simulated seconds since the robot pipeline started
+    pub elapsed_secs: f32,
+    /// Hero path length in world pixels
+    pub distance_travelled: f32,
+    /// Hero ↔ `MergedWall` contacts started
+    pub wall_contacts: usize,
+    /// `PathPlan`s attached to the hero by the planner
+    pub replans: usize,
+    /// `PathPlan`s removed because the last waypoint was reached
+    pub paths_completed: usize,
+    /// `PathPlan`s removed because the grid showed them blocked
+    pub paths_invalidated: usize,
+    /// Times the planner ran out of frontiers and cleared the explored area
+    pub grid_resets: usize,
+    /// Seconds spent turning on the spot
+    pub rotating_in_place_secs: f32,
+    /// Seconds spent without leaving a small radius (see `SIM_STUCK_*`)
+    pub stuck_secs: f32,
+    /// Number of separate stalls
+    pub stuck_events: usize,
+    /// Planned length of completed paths, in world pixels
+    pub planned_path_length: f32,
+    /// Distance actually driven while following those paths, in world pixels
+    pub driven_path_length: f32,
+    /// Sim time at which the last collectible was picked up
+    pub completed_at_secs: Option<f32>,
+    /// Distance between the wheel-odometry pose and the true pose now, in world pixels
+    pub odometry_error_px: f32,
+    /// Worst odometry position error so far, in world pixels
+    pub odometry_error_max_px: f32,
+    /// Odometry heading minus true heading now (degrees)
+    pub odometry_heading_error_deg: f32,
+    /// Distance between the localizer's pose estimate and the true pose now,
+    /// in world pixels (`None` without a localizer)
+    pub localization_error_px: Option<f32>,
+    /// Times the hero was kidnapped (teleported)
+    pub kidnaps: usize,
+    /// Sim time of the last kidnap
+    pub kidnapped_at_secs: Option<f32>,
+    /// Seconds from the last kidnap until the localizer found the hero again
+    pub kidnap_recovery_secs: Option<f32>,
+    /// Distance between the pose the hero maps with (transform tree) and the
+    /// true pose, at the last step, in world pixels
+    pub pose_error_px: f32,
+    /// Worst mapped-with pose error so far, in world pixels
+    pub pose_error_max_px: f32,
+    /// Sum of squared pose errors over `pose_error_steps` (for the RMSE)
+    pub pose_error_sq_sum: f32,
+    pub pose_error_steps: usize,
+    /// Loop closures accepted by the pose-graph back end
+    pub loop_closures: usize,
+    /// Times the grid was re-rendered from optimized keyframes
+    pub map_rebuilds: usize,
+    /// Mean distance between the EKF's landmark estimates and the true
+    /// collectible positions, in world pixels (`None` without EKF SLAM)
+    pub landmark_error_px: Option<f32>,
+    /// Landmarks in the EKF state
+    pub landmarks_mapped: usize,
+}
+
+impl SimMetrics {
+    pub fn path_removals(&self) -> usize {
+        self.paths_completed + self.paths_invalidated
+    }
+
+    /// Planned ÷ driven length over completed paths. Close to 1.0 means the hero
+    /// drove the plan; it can exceed 1.0 because waypoints are reached from a distance.
+    pub fn path_efficiency(&self) -> Option<f32> {
+        (self.driven_path_length > 0.0).then(|| self.planned_path_length / self.driven_path_length)
+    }
+
+    /// Root-mean-square mapped-with pose error over the episode, in world pixels
+    pub fn pose_error_rmse_px(&self) -> f32 {
+        if self.pose_error_steps == 0 {
+            return 0.0;
+        }
+        (self.pose_error_sq_sum / self.pose_error_steps as f32).sqrt()
+    }
+
+    pub fn record_path_completed(&mut self, plan: &PathPlan) {
+        self.paths_completed += 1;
+        self.planned_path_length += plan.planned_length;
+        self.driven_path_length += plan.driven_length;
+    }
+}
+
+/// Where the hero was when it last made real progress.
+#[derive(Default)]
+pub struct StallAnchor {
+    pos: Option<Vec2>,
+    since_secs: f32,
+    stuck: bool,
+}
+
+/// Integrates sim time, hero distance, turning and stalls once per fixed step
+/// (after Rapier writeback).
+pub fn track_motion_metrics_system(
+    time: Res<Time>,
+    stats: Res<CollectionStats>,
+    mut metrics: ResMut<SimMetrics>,
+    mut elapsed: Local<Duration>,
+    mut last_pos: Local<Option<Vec2>>,
+    mut anchor: Local<StallAnchor>,
+    mut hero: Query<(&GlobalTransform, &Velocity, Option<&mut PathPlan>), With<HeroController>>,
+) {
+    // Summed as a `Duration` so the sim clock lands exactly on step
+    // multiples instead of drifting with f32 rounding
+    let dt = time.delta_seconds();
+    *elapsed += time.delta();
+    metrics.elapsed_secs = elapsed.as_secs_f32();
+
+    if let Ok((xform, velocity, plan)) = hero.get_single_mut() {
+        let pos = xform.translation().truncate();
+        if let Some(prev) = *last_pos {
+            let step = pos.distance(prev);
+            metrics.distance_travelled += step;
+            if let Some(mut plan) = plan {
+                plan.driven_length += step;
+            }
+        }
+        *last_pos = Some(pos);
+
+        if velocity.angvel.abs() > SIM_ROTATE_MIN_ANGVEL
+            && velocity.linvel.length() < SIM_ROTATE_MAX_LINVEL
+        {
+            metrics.rotating_in_place_secs += dt;
+        }
+
+        let now = metrics.elapsed_secs;
+        match anchor.pos {
+            Some(a) if a.distance(pos) <= SIM_STUCK_RADIUS_PX => {
+                if now - anchor.since_secs > SIM_STUCK_WINDOW_SECS {
+                    if !anchor.stuck {
+                        anchor.stuck = true;
+                        metrics.stuck_events += 1;
+                        // Count the window that led up to the stall as well
+                        metrics.stuck_secs += SIM_STUCK_WINDOW_SECS;
+                    }
+                    metrics.stuck_secs += dt;
+                }
+            }
+            _ => {
+                *anchor = StallAnchor {
+                    pos: Some(pos),
+                    since_secs: now,
+                    stuck: false,
+                };
+            }
+        }
+    }
+
+    if metrics.completed_at_secs.is_none() && stats.total > 0 && stats.collected >= stats.total {
+        metrics.completed_at_secs = Some(metrics.elapsed_secs);
+    }
+}
+
+/// Compares the pose the hero maps and navigates with against ground truth,
+/// once per fixed step (after the grid update)
+pub fn track_pose_error_system(
+    mut metrics: ResMut<SimMetrics>,
+    hero: Query<(&GlobalTransform, &TransformTree), With<HeroController>>,
+) {
+    let Ok((transform, tree)) = hero.get_single() else {
+        return;
+    };
+    let error = tree
+        .map_to_base()
+        .translation
+        .distance(Pose2d::from_global(transform).translation);
+    metrics.pose_error_px = error;
+    metrics.pose_error_max_px = metrics.pose_error_max_px.max(error);
+    metrics.pose_error_sq_sum += error * error;
+    metrics.pose_error_steps += 1;
+}
+
+/// Compares the EKF's landmark estimates with where the collectibles are.
+/// Positions are remembered, so picked-up collectibles still count.
+pub fn track_landmark_error_system(
+    mut metrics: ResMut<SimMetrics>,
+    mut truth: Local<HashMap<Entity, Vec2>>,
+    hero: Query<&EkfSlam, With<HeroController>>,
+    collectibles: Query<(Entity, &GlobalTransform), With<Collectible>>,
+) {
+    let Ok(ekf) = hero.get_single() else {
+        return;
+    };
+    for (entity, transform) in collectibles.iter() {
+        truth
+            .entry(entity)
+            .or_insert_with(|| transform.translation().truncate());
+    }
+
+    let errors: Vec<f32> = ekf
+        .landmarks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, entity)| Some(truth.get(entity)?.distance(ekf.landmark(i))))
+        .collect();
+    metrics.landmarks_mapped = ekf.landmarks.len();
+    metrics.landmark_error_px =
+        (!errors.is_empty()).then(|| errors.iter().sum::<f32>() / errors.len() as f32);
+}
+
+pub fn count_wall_contacts_system(
+    mut collision_events: EventReader<CollisionEvent>,
+    mut metrics: ResMut<SimMetrics>,
+    heroes: Query<(), With<HeroController>>,
+    walls: Query<(), With<MergedWall>>,
+) {
+    for event in collision_events.read() {
+        if let CollisionEvent::Started(e1, e2, _) = event {
+            let hero_wall = (heroes.contains(*e1) && walls.contains(*e2))
+                || (heroes.contains(*e2) && walls.contains(*e1));
+            if hero_wall {
+                metrics.wall_contacts += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use bevy_rapier2d::prelude::PhysicsSet;
+
+use super::coverage::{update_coverage_system, CoverageStats};
+use super::kidnap::{kidnap_keys_system, kidnap_system, KidnapRequest, Kidnapped};
+use super::sim_metrics::{
+    count_wall_contacts_system, track_landmark_error_system, track_motion_metrics_system,
+    track_pose_error_system, SimMetrics,
+};
+use super::sim_seed::SimSeed;
+use super::sim_time::{
+    apply_sim_timing_system, halt_fixed_steps_system, max_speed_system, sim_speed_keys_system,
+    MaxSpeedSteps, SimTiming,
+};
+use crate::app::{RobotSet, SimMode, SimTransformSet};
+use crate::systems::level::level_ready;
+
+// ┌────────────────────────────────────────────────────────────────────────────┐
+// │                              SIM PLUGIN OVERVIEW                           │
+// └────────────────────────────────────────────────────────────────────────────┘
+//
+// Owns the "simulation harness" side of Pick.e, as opposed to the robot itself:
+//
+// ▶ `SimSeed` (sim_seed.rs)
+//    - Single seed from which every random consumer derives its own RNG stream.
+//    - Random by default; `make_deterministic` pins it.
+//
+// ▶ `SimTiming` (sim_time.rs)
+//    - The robot pipeline and Rapier run in `FixedUpdate` at `rate_hz`.
+//    - `speed` scales virtual time: paused, 1x, 10x, ... or `Max`, which packs
+//      as many fixed steps into each frame as the frame budget allows.
+//    - Keys (windowed): P pause, 1 = 1x, 2 = 10x, 3 = max.
+//
+// ▶ `SimMetrics` (sim_metrics.rs)
+//    - Episode measurements (sim time, distance, wall contacts, replans, path
+//      removals, grid resets, time rotating in place / stuck, path efficiency,
+//      time to collect everything, odometry / localization / mapped-with pose
+//      error against ground truth) used by the HUD and the batch runner.
+//    - The auto-nav systems record path and grid events directly into it.
+//
+// ▶ `CoverageStats` (coverage.rs)
+//    - Explored % = hero-mapped free cells / reachable free cells in the level
+//      mask (`LevelMask::reachable_from` the spawn, flooding only tiles that
+//      are not `is_blocked`, so walls, no-go and drop tiles never count).
+//
+// ▶ Kidnapping (kidnap.rs)
+//    - A `KidnapRequest` (K when windowed, `EpisodeConfig::kidnap_at_secs` in
+//      batch runs) teleports the hero to a random reachable spot, for testing
+//      localization recovery. `Kidnapped` tells odometry to ignore the jump.
+//
+// ▶ Deterministic stepping
+//    - `make_deterministic` replaces wall-clock time with exactly one fixed step
+//      per update, so two runs with the same seed produce bit-identical
+//      trajectories, collectible layouts and stats.
+//    - `SimHaltAt` stops fixed stepping on an exact step (and on completion),
+//      so an episode's end doesn't depend on how many steps `Max` packs into
+//      its last frame.
+
+pub struct SimPlugin;
+
+impl Plugin for SimPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SimSeed>()
+            .init_resource::<SimTiming>()
+            .init_resource::<MaxSpeedSteps>()
+            .add_systems(PreUpdate, apply_sim_timing_system)
+            .add_systems(FixedLast, halt_fixed_steps_system)
+            .add_systems(Last, max_speed_system);
+
+        app.init_resource::<SimMetrics>().add_systems(
+            FixedUpdate,
+            (track_motion_metrics_system, count_wall_contacts_system)
+                .after(SimTransformSet)
+                .run_if(level_ready),
+        );
+        app.add_systems(
+            FixedUpdate,
+            (track_pose_error_system, track_landmark_error_system)
+                .after(RobotSet::Map)
+                .before(RobotSet::Plan)
+                .run_if(level_ready),
+        );
+
+        app.add_event::<KidnapRequest>()
+            .add_event::<Kidnapped>()
+            .add_systems(
+                FixedUpdate,
+                kidnap_system
+                    .after(RobotSet::Act)
+                    .before(PhysicsSet::SyncBackend)
+                    .run_if(level_ready),
+            );
+
+        app.init_resource::<CoverageStats>()
+            .add_systems(Update, update_coverage_system.run_if(level_ready));
+
+        if app.world.resource::<SimMode>().is_windowed() {
+            app.add_systems(Update, (sim_speed_keys_system, kidnap_keys_system));
+        }
+    }
+}
+
+/// Pins the seed and replaces wall-clock time with one fixed step per `App::update`
+/// (more at higher speeds, but always whole steps).
+///
+/// Call after building the app and after any change to `SimTiming::rate_hz`.
+pub fn make_deterministic(app: &mut App, seed: u64) {
+    let timing = *app.world.resource::<SimTiming>();
+    let dt = Duration::from_secs_f64(timing.timestep_seconds());
+
+    app.insert_resource(SimSeed(seed));
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(dt));
+
+    info!(
+        "[Sim] Deterministic mode: seed={seed}, dt={:.4}s",
+        dt.as_secs_f32()
+    );
+}
@@ -0,0 +1,44 @@
+use bevy::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// Seed for all simulation randomness.
+///
+/// Each consumer derives its own stream via `rng(name)`, so adding a new random
+/// consumer does not shift the numbers any existing one sees.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimSeed(pub u64);
+
+impl SimSeed {
+    /// A fresh, non-reproducible seed (the default for interactive runs)
+    pub fn from_entropy() -> Self {
+        Self(rand::random())
+    }
+
+    /// Seeded RNG for one named consumer (e.g. "collectibles")
+    pub fn rng(&self, stream: &str) -> StdRng {
+        StdRng::seed_from_u64(self.0 ^ fnv1a(stream.as_bytes()))
+    }
+}
+
+impl Default for SimSeed {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+/// Standard normal sample (Box–Muller)
+pub fn gaussian(rng: &mut impl Rng) -> f32 {
+    let u1: f32 = rng.gen_range(f32::EPSILON..1.0);
+    let u2: f32 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (std::f32::consts::TAU * u2).cos()
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
@@ -0,0 +1,196 @@
+use bevy::prelude::*;
+use bevy::time::Real;
+use bevy_rapier2d::prelude::{RapierConfiguration, TimestepMode};
+
+use super::sim_constants::*;
+use super::sim_metrics::SimMetrics;
+
+/// Simulation time scale relative to wall-clock time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SimSpeed {
+    Paused,
+    /// Fixed multiplier (1.0 = real-time)
+    Scaled(f32),
+    /// As many fixed steps per frame as fit in `SIM_MAX_SPEED_FRAME_BUDGET`
+    Max,
+}
+
+impl Default for SimSpeed {
+    fn default() -> Self {
+        SimSpeed::Scaled(1.0)
+    }
+}
+
+impl SimSpeed {
+    /// Parses "pause", "max" or a multiplier such as "1", "10" or "0.5"
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "pause" | "paused" => Some(SimSpeed::Paused),
+            "max" => Some(SimSpeed::Max),
+            _ => text
+                .trim_end_matches('x')
+                .parse::<f32>()
+                .ok()
+                .filter(|s| *s > 0.0)
+                .map(SimSpeed::Scaled),
+        }
+    }
+}
+
+/// Fixed-step rate and time scale for the robot pipeline and Rapier.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct SimTiming {
+    pub rate_hz: f64,
+    pub speed: SimSpeed,
+}
+
+impl Default for SimTiming {
+    fn default() -> Self {
+        Self {
+            rate_hz: SIM_RATE_HZ,
+            speed: SimSpeed::default(),
+        }
+    }
+}
+
+impl SimTiming {
+    pub fn timestep_seconds(&self) -> f64 {
+        1.0 / self.rate_hz
+    }
+}
+
+/// Adaptive step count used while `SimSpeed::Max` is active
+#[derive(Resource, Debug)]
+pub struct MaxSpeedSteps(pub f32);
+
+impl Default for MaxSpeedSteps {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// Sim time at which fixed stepping halts for the rest of the frame (and
+/// each following frame), so a batch episode stops, or is kidnapped, on an
+/// exact step whatever the `SimSpeed`. Stepping also halts once everything
+/// is collected. Absent = never halt.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct SimHaltAt(pub f32);
+
+impl SimHaltAt {
+    /// Whether the last fixed step reached the halt (within half a step, so
+    /// float drift in the sim clock can't add or lose one) or finished the level
+    pub fn reached(&self, metrics: &SimMetrics, timing: &SimTiming) -> bool {
+        let half_step = 0.5 * timing.timestep_seconds() as f32;
+        metrics.elapsed_secs + half_step >= self.0 || metrics.completed_at_secs.is_some()
+    }
+}
+
+/// Drops the frame's remaining fixed steps once `SimHaltAt` is reached
+pub fn halt_fixed_steps_system(
+    halt_at: Option<Res<SimHaltAt>>,
+    timing: Res<SimTiming>,
+    metrics: Res<SimMetrics>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+) {
+    let Some(halt_at) = halt_at else {
+        return;
+    };
+    if halt_at.reached(&metrics, &timing) {
+        let overstep = fixed_time.overstep();
+        fixed_time.discard_overstep(overstep);
+    }
+}
+
+/// Pushes `SimTiming` into Bevy's fixed/virtual clocks and Rapier's timestep.
+pub fn apply_sim_timing_system(
+    timing: Res<SimTiming>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut max_steps: ResMut<MaxSpeedSteps>,
+) {
+    if !timing.is_changed() {
+        return;
+    }
+
+    let dt = timing.timestep_seconds();
+    fixed_time.set_timestep_seconds(dt);
+    rapier_config.timestep_mode = TimestepMode::Fixed {
+        dt: dt as f32,
+        substeps: 1,
+    };
+
+    match timing.speed {
+        SimSpeed::Paused => virtual_time.pause(),
+        SimSpeed::Scaled(scale) => {
+            virtual_time.unpause();
+            virtual_time.set_max_delta(SIM_DEFAULT_MAX_DELTA);
+            virtual_time.set_relative_speed(scale);
+        }
+        SimSpeed::Max => {
+            virtual_time.unpause();
+            max_steps.0 = 1.0;
+        }
+    }
+
+    info!(
+        "[Sim] Rate {:.0} Hz, speed {:?}",
+        timing.rate_hz, timing.speed
+    );
+}
+
+/// While running at `SimSpeed::Max`, retunes the virtual clock each frame so the
+/// fixed loop runs as many steps as the frame budget allows.
+pub fn max_speed_system(
+    timing: Res<SimTiming>,
+    real_time: Res<Time<Real>>,
+    mut virtual_time: ResMut<Time<Virtual>>,
+    mut max_steps: ResMut<MaxSpeedSteps>,
+) {
+    if timing.speed != SimSpeed::Max {
+        return;
+    }
+
+    let frame = real_time.delta();
+    if frame.is_zero() {
+        return;
+    }
+
+    if frame < SIM_MAX_SPEED_FRAME_BUDGET {
+        max_steps.0 = (max_steps.0 * 1.25 + 1.0).min(SIM_MAX_SPEED_MAX_STEPS);
+    } else {
+        max_steps.0 = (max_steps.0 * 0.8).max(1.0);
+    }
+
+    // Virtual delta is clamped to `max_delta` *before* scaling, so open it up
+    // enough that a slow frame doesn't silently cap the speed.
+    let step = timing.timestep_seconds();
+    let wanted = step * max_steps.0.floor() as f64;
+    virtual_time.set_max_delta(frame.max(SIM_MAX_SPEED_FRAME_BUDGET) * 2);
+    virtual_time.set_relative_speed_f64(wanted / frame.as_secs_f64());
+}
+
+/// Keyboard time controls: P pause/resume, 1 = 1x, 2 = 10x, 3 = as fast as possible.
+pub fn sim_speed_keys_system(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut timing: ResMut<SimTiming>,
+    mut resume_speed: Local<Option<SimSpeed>>,
+) {
+    if keys.just_pressed(KeyCode::KeyP) {
+        if timing.speed == SimSpeed::Paused {
+            timing.speed = resume_speed.take().unwrap_or_default();
+        } else {
+            *resume_speed = Some(timing.speed);
+            timing.speed = SimSpeed::Paused;
+        }
+    }
+    if keys.just_pressed(KeyCode::Digit1) {
+        timing.speed = SimSpeed::Scaled(1.0);
+    }
+    if keys.just_pressed(KeyCode::Digit2) {
+        timing.speed = SimSpeed::Scaled(SIM_FAST_FORWARD_SCALE);
+    }
+    if keys.just_pressed(KeyCode::Digit3) {
+        timing.speed = SimSpeed::Max;
+    }
+}
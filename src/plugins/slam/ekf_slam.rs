@@ -0,0 +1,284 @@
+use bevy::prelude::*;
+
+use super::slam_constants::*;
+use crate::components::frames::{wrap_angle, Pose2d, TransformTree};
+use crate::components::landmark::{LandmarkDetector, LandmarkObservation};
+use crate::components::odometry::{Odometry, PoseSource};
+use crate::constants::METERS_PER_PIXEL;
+
+/// Landmark EKF SLAM: robot pose and landmark positions estimated jointly
+/// by one extended Kalman filter.
+///
+/// The state is `[x, y, yaw, l0.x, l0.y, l1.x, l1.y, ...]` in the map frame,
+/// growing as landmarks are first seen; the covariance is stored dense.
+#[derive(Component, Debug, Clone, Default)]
+pub struct EkfSlam {
+    pub mean: Vec<f32>,
+    /// Row-major, `mean.len()` × `mean.len()`
+    pub covariance: Vec<f32>,
+    /// The collectible behind each landmark, in state order
+    pub landmarks: Vec<Entity>,
+    /// Odometry pose at the last prediction
+    last_odom: Option<Pose2d>,
+}
+
+impl EkfSlam {
+    /// Estimated robot pose (map frame)
+    pub fn pose(&self) -> Pose2d {
+        Pose2d::new(Vec2::new(self.mean[0], self.mean[1]), self.mean[2])
+    }
+
+    /// Estimated position of the `i`th landmark
+    pub fn landmark(&self, i: usize) -> Vec2 {
+        Vec2::new(self.mean[3 + 2 * i], self.mean[4 + 2 * i])
+    }
+
+    /// x/y covariance of the state entries starting at `i` (0 = robot)
+    pub fn position_covariance(&self, i: usize) -> (f32, f32, f32) {
+        (self.cov(i, i), self.cov(i, i + 1), self.cov(i + 1, i + 1))
+    }
+
+    fn cov(&self, row: usize, col: usize) -> f32 {
+        self.covariance[row * self.mean.len() + col]
+    }
+
+    /// Moves the robot by `step` (base frame), growing its uncertainty
+    fn predict(&mut self, step: Pose2d) {
+        let n = self.mean.len();
+        let (sin, cos) = self.mean[2].sin_cos();
+        let (dx, dy) = (step.translation.x, step.translation.y);
+        self.mean[0] += cos * dx - sin * dy;
+        self.mean[1] += sin * dx + cos * dy;
+        self.mean[2] = wrap_angle(self.mean[2] + step.yaw);
+
+        // P ← G P Gᵀ + Q, with G the identity except for d(x, y)/d(yaw);
+        // only the robot's rows and columns change
+        let g02 = -sin * dx - cos * dy;
+        let g12 = cos * dx - sin * dy;
+        for col in 0..n {
+            let yaw_row = self.covariance[2 * n + col];
+            self.covariance[col] += g02 * yaw_row;
+            self.covariance[n + col] += g12 * yaw_row;
+        }
+        for row in 0..n {
+            let yaw_col = self.covariance[row * n + 2];
+            self.covariance[row * n] += g02 * yaw_col;
+            self.covariance[row * n + 1] += g12 * yaw_col;
+        }
+
+        let metres = step.translation.length() * METERS_PER_PIXEL;
+        let drive_variance = SLAM_EKF_DRIVE_SIGMA_PX_PER_SQRT_M.powi(2) * metres;
+        let heading_variance = SLAM_EKF_HEADING_SIGMA_DEG_PER_SQRT_M.to_radians().powi(2) * metres
+            + SLAM_EKF_TURN_SIGMA_DEG_PER_SQRT_RAD.to_radians().powi(2) * step.yaw.abs();
+        self.covariance[0] += drive_variance;
+        self.covariance[n + 1] += drive_variance;
+        self.covariance[2 * n + 2] += heading_variance;
+    }
+
+    /// Adds a landmark first seen at `range` / `bearing` from the robot
+    fn add_landmark(&mut self, landmark: Entity, range: f32, bearing: f32, noise: Mat2) {
+        let n = self.mean.len();
+        let angle = self.mean[2] + bearing;
+        let (sin, cos) = angle.sin_cos();
+        self.mean.push(self.mean[0] + range * cos);
+        self.mean.push(self.mean[1] + range * sin);
+        self.landmarks.push(landmark);
+
+        // Jacobians of the new position w.r.t. the robot pose and the sighting
+        let g_pose = [[1.0, 0.0, -range * sin], [0.0, 1.0, range * cos]];
+        let g_sighting = Mat2::from_cols(Vec2::new(cos, sin), Vec2::new(-range * sin, range * cos));
+
+        let mut covariance = vec![0.0; (n + 2) * (n + 2)];
+        for row in 0..n {
+            covariance[row * (n + 2)..row * (n + 2) + n]
+                .copy_from_slice(&self.covariance[row * n..(row + 1) * n]);
+        }
+        // Cross terms: G_pose · P(robot, everything)
+        for (k, g) in g_pose.iter().enumerate() {
+            for col in 0..n {
+                let value: f32 = (0..3).map(|r| g[r] * self.covariance[r * n + col]).sum();
+                covariance[(n + k) * (n + 2) + col] = value;
+                covariance[col * (n + 2) + n + k] = value;
+            }
+        }
+        // Own block: G_pose · P_rr · G_poseᵀ + G_sighting · R · G_sightingᵀ
+        let sighting = g_sighting * noise * g_sighting.transpose();
+        for (k, gk) in g_pose.iter().enumerate() {
+            for (l, gl) in g_pose.iter().enumerate() {
+                let mut value = sighting.col(l)[k];
+                for (r, gr) in gk.iter().enumerate() {
+                    for (c, gc) in gl.iter().enumerate() {
+                        value += gr * self.covariance[r * n + c] * gc;
+                    }
+                }
+                covariance[(n + k) * (n + 2) + n + l] = value;
+            }
+        }
+
+        self.covariance = covariance;
+    }
+
+    /// Corrects the state with a sighting of landmark `i`, unless it fails
+    /// the innovation gate
+    fn update(&mut self, i: usize, range: f32, bearing: f32, noise: Mat2) {
+        let n = self.mean.len();
+        let l = 3 + 2 * i;
+        let delta = self.landmark(i) - Vec2::new(self.mean[0], self.mean[1]);
+        let q = delta.length_squared().max(f32::EPSILON);
+        let distance = q.sqrt();
+
+        // Nonzero columns of H: robot x, y, yaw, then the landmark's x, y
+        let columns = [0, 1, 2, l, l + 1];
+        let h = [
+            [
+                -delta.x / distance,
+                -delta.y / distance,
+                0.0,
+                delta.x / distance,
+                delta.y / distance,
+            ],
+            [delta.y / q, -delta.x / q, -1.0, -delta.y / q, delta.x / q],
+        ];
+
+        // P Hᵀ (n × 2), then S = H P Hᵀ + R
+        let p_ht: Vec<Vec2> = (0..n)
+            .map(|row| {
+                let mut value = Vec2::ZERO;
+                for (c, &col) in columns.iter().enumerate() {
+                    let p = self.covariance[row * n + col];
+                    value += Vec2::new(h[0][c], h[1][c]) * p;
+                }
+                value
+            })
+            .collect();
+        let mut s = noise;
+        for (c, &col) in columns.iter().enumerate() {
+            s.x_axis += p_ht[col].x * Vec2::new(h[0][c], h[1][c]);
+            s.y_axis += p_ht[col].y * Vec2::new(h[0][c], h[1][c]);
+        }
+        let off_diagonal = 0.5 * (s.x_axis.y + s.y_axis.x);
+        s.x_axis.y = off_diagonal;
+        s.y_axis.x = off_diagonal;
+        let s_inv = s.inverse();
+
+        let expected_bearing = delta.y.atan2(delta.x) - self.mean[2];
+        let innovation = Vec2::new(range - distance, wrap_angle(bearing - expected_bearing));
+        if innovation.dot(s_inv * innovation) > SLAM_EKF_GATE {
+            return;
+        }
+
+        // K = P Hᵀ S⁻¹; mean += K ν; P -= K S Kᵀ = P Hᵀ S⁻¹ (P Hᵀ)ᵀ
+        let gains: Vec<Vec2> = p_ht.iter().map(|row| s_inv.transpose() * *row).collect();
+        for (row, gain) in gains.iter().enumerate() {
+            self.mean[row] += gain.dot(innovation);
+        }
+        self.mean[2] = wrap_angle(self.mean[2]);
+        // (one triangle, mirrored: rounding must not make P asymmetric)
+        for (row, gain) in gains.iter().enumerate() {
+            for (col, p_ht_col) in p_ht.iter().enumerate().skip(row) {
+                let value = self.covariance[row * n + col] - gain.dot(*p_ht_col);
+                self.covariance[row * n + col] = value;
+                self.covariance[col * n + row] = value;
+            }
+        }
+    }
+}
+
+/// Runs the EKF for each robot with an `EkfSlam`: predicts with the
+/// odometry step, then corrects with this step's landmark sightings (known
+/// data association: each collectible is its own landmark).
+///
+/// With `PoseSource::Odometry` the estimate becomes the map → odom
+/// correction, so mapping and navigation run on it.
+#[allow(clippy::type_complexity)]
+pub fn ekf_slam_system(
+    mut observations: EventReader<LandmarkObservation>,
+    mut query: Query<(
+        Entity,
+        &Odometry,
+        Option<&PoseSource>,
+        Option<&LandmarkDetector>,
+        &mut EkfSlam,
+        &mut TransformTree,
+    )>,
+) {
+    let observations: Vec<&LandmarkObservation> = observations.read().collect();
+
+    for (entity, odometry, source, detector, mut ekf, mut tree) in query.iter_mut() {
+        let Some(odom_pose) = odometry.pose else {
+            continue;
+        };
+        // Starts where odometry starts, certain of it (the map is laid there)
+        let Some(last_odom) = ekf.last_odom else {
+            ekf.mean = vec![
+                odom_pose.translation.x,
+                odom_pose.translation.y,
+                odom_pose.yaw,
+            ];
+            ekf.covariance = vec![0.0; 9];
+            ekf.last_odom = Some(odom_pose);
+            continue;
+        };
+        ekf.predict(last_odom.inverse() * odom_pose);
+        ekf.last_odom = Some(odom_pose);
+
+        let detector = detector.copied().unwrap_or_default();
+        let noise = Mat2::from_diagonal(Vec2::new(
+            detector.range_sigma_px * detector.range_sigma_px,
+            detector.bearing_sigma_deg.to_radians().powi(2),
+        ));
+        for observation in observations.iter().filter(|o| o.entity == entity) {
+            match ekf
+                .landmarks
+                .iter()
+                .position(|&l| l == observation.landmark)
+            {
+                Some(i) => ekf.update(i, observation.range, observation.bearing, noise),
+                None => ekf.add_landmark(
+                    observation.landmark,
+                    observation.range,
+                    observation.bearing,
+                    noise,
+                ),
+            }
+        }
+
+        if source.copied().unwrap_or_default() == PoseSource::Odometry {
+            tree.map_to_odom = ekf.pose() * odom_pose.inverse();
+        }
+    }
+}
+
+/// 2σ covariance ellipses: the robot in yellow, landmarks in cyan
+pub fn ekf_slam_debug_draw_system(query: Query<&EkfSlam>, mut gizmos: Gizmos) {
+    for ekf in query.iter() {
+        if ekf.mean.len() < 3 {
+            continue;
+        }
+        let pose = ekf.pose();
+        let (angle, half_sizes) = covariance_ellipse(ekf.position_covariance(0));
+        gizmos.ellipse_2d(pose.translation, angle, half_sizes, Color::YELLOW);
+        gizmos.line_2d(
+            pose.translation,
+            pose.transform_point(Vec2::X * 24.0),
+            Color::YELLOW,
+        );
+
+        for i in 0..ekf.landmarks.len() {
+            let (angle, half_sizes) = covariance_ellipse(ekf.position_covariance(3 + 2 * i));
+            let position = ekf.landmark(i);
+            gizmos.ellipse_2d(position, angle, half_sizes, Color::CYAN);
+            gizmos.circle_2d(position, 2.0, Color::CYAN);
+        }
+    }
+}
+
+/// Orientation and 2σ half-axes of an x/y covariance `(xx, xy, yy)`
+fn covariance_ellipse((xx, xy, yy): (f32, f32, f32)) -> (f32, Vec2) {
+    let half_trace = 0.5 * (xx + yy);
+    let spread = (0.25 * (xx - yy).powi(2) + xy * xy).sqrt();
+    let major = (half_trace + spread).max(0.0).sqrt();
+    let minor = (half_trace - spread).max(0.0).sqrt();
+    let angle = 0.5 * (2.0 * xy).atan2(xx - yy);
+    (angle, Vec2::new(major, minor) * 2.0)
+}
@@ -0,0 +1,5 @@
+pub mod ekf_slam;
+pub mod pose_graph;
+pub mod scan_matcher;
+pub mod slam_constants;
+pub mod slam_plugin;
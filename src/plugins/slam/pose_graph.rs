@@ -0,0 +1,411 @@
+use bevy::prelude::*;
+
+use super::scan_matcher::{match_scan, scan_points, ScanMatchPose, ScanMatcher, SearchWindow};
+use super::slam_constants::*;
+use crate::components::frames::{wrap_angle, Pose2d, TransformTree};
+use crate::components::lidar::{LaserScan, LidarConfig};
+use crate::components::occupancy_grid::{OccupancyGrid, OccupancyGridReset};
+use crate::components::odometry::PoseSource;
+use crate::plugins::sim::sim_metrics::SimMetrics;
+use crate::systems::robot::occupancy_grid::integrate_scan;
+
+/// A scan kept by the pose graph, with where the graph currently puts it
+#[derive(Debug, Clone)]
+pub struct Keyframe {
+    /// Graph estimate of the base pose at the scan (map frame)
+    pub pose: Pose2d,
+    /// Odometry pose at the scan
+    pub odom: Pose2d,
+    pub scan: LaserScan,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Consecutive keyframes, as the front end saw them
+    Sequential,
+    /// A revisited place, found by matching against an older keyframe
+    LoopClosure,
+}
+
+/// Measured pose of keyframe `to` in the frame of keyframe `from`
+#[derive(Debug, Clone, Copy)]
+pub struct GraphEdge {
+    pub from: usize,
+    pub to: usize,
+    pub measurement: Pose2d,
+    pub kind: EdgeKind,
+}
+
+/// Pose-graph SLAM back end for one robot (needs a `ScanMatcher`).
+///
+/// Keyframes are taken from the front end's `ScanMatchPose`s; when one
+/// closes a loop the graph is optimized and the occupancy grid re-rendered
+/// from the keyframes at their corrected poses.
+#[derive(Component, Debug, Clone, Default)]
+pub struct PoseGraph {
+    pub keyframes: Vec<Keyframe>,
+    pub edges: Vec<GraphEdge>,
+    /// Keyframes from this index on were taken after the last grid reset;
+    /// older ones only contribute walls when the grid is re-rendered
+    free_from: usize,
+}
+
+/// Loop closure search: wider than the front end's, since drift builds up
+/// over a loop
+const LOOP_WINDOW: SearchWindow = SearchWindow {
+    search_px: SLAM_LOOP_SEARCH_PX,
+    search_deg: SLAM_LOOP_SEARCH_DEG,
+    step_px: SLAM_LOOP_STEP_PX,
+    step_deg: SLAM_LOOP_STEP_DEG,
+    prior_sigma_px: SLAM_LOOP_PRIOR_SIGMA_PX,
+    prior_sigma_deg: SLAM_LOOP_PRIOR_SIGMA_DEG,
+};
+
+/// Adds keyframes, looks for loop closures and, after one, optimizes the
+/// graph and re-renders the grid.
+///
+/// The front end is re-anchored on the corrected latest keyframe so later
+/// scans continue from the optimized trajectory. On ground truth
+/// (`PoseSource::GroundTruth`) the graph is still built and optimized, but
+/// the grid and transform tree are left alone.
+#[allow(clippy::type_complexity)]
+pub fn pose_graph_system(
+    mut matches: EventReader<ScanMatchPose>,
+    mut scans: EventReader<LaserScan>,
+    mut resets: EventReader<OccupancyGridReset>,
+    mut metrics: ResMut<SimMetrics>,
+    mut query: Query<(
+        Entity,
+        Option<&PoseSource>,
+        Option<&LidarConfig>,
+        &mut PoseGraph,
+        &mut ScanMatcher,
+        &mut OccupancyGrid,
+        &mut TransformTree,
+    )>,
+) {
+    let matches: Vec<&ScanMatchPose> = matches.read().collect();
+    let scans: Vec<&LaserScan> = scans.read().collect();
+    let resets: Vec<&OccupancyGridReset> = resets.read().collect();
+
+    for (entity, source, config, mut graph, mut matcher, mut grid, mut tree) in query.iter_mut() {
+        let config = config.copied().unwrap_or_default();
+        let base_to_laser = config.base_to_laser();
+        if resets.iter().any(|reset| reset.entity == entity) {
+            graph.free_from = graph.keyframes.len();
+        }
+
+        let mut closed_loop = false;
+        for matched in matches.iter().filter(|matched| matched.entity == entity) {
+            if !is_new_keyframe(&graph, matched.pose) {
+                continue;
+            }
+            let Some(scan) = scans
+                .iter()
+                .find(|scan| scan.entity == entity && scan.stamp == matched.stamp)
+            else {
+                continue;
+            };
+
+            let index = graph.keyframes.len();
+            if let Some(last) = graph.keyframes.last() {
+                let measurement = last.pose.inverse() * matched.pose;
+                graph.edges.push(GraphEdge {
+                    from: index - 1,
+                    to: index,
+                    measurement,
+                    kind: EdgeKind::Sequential,
+                });
+            }
+            graph.keyframes.push(Keyframe {
+                pose: matched.pose,
+                odom: matched.odom,
+                scan: (*scan).clone(),
+            });
+
+            if let Some(edge) = find_loop_closure(&graph, index, grid.resolution, &config) {
+                info!("[SLAM] Loop closure: keyframe {} ↔ {}", edge.from, edge.to);
+                graph.edges.push(edge);
+                metrics.loop_closures += 1;
+                closed_loop = true;
+            }
+        }
+        if !closed_loop {
+            continue;
+        }
+
+        let before: Vec<Pose2d> = graph
+            .keyframes
+            .iter()
+            .map(|keyframe| keyframe.pose)
+            .collect();
+        optimize(&mut graph);
+        let max_shift = graph
+            .keyframes
+            .iter()
+            .zip(&before)
+            .map(|(keyframe, pose)| keyframe.pose.translation.distance(pose.translation))
+            .fold(0.0, f32::max);
+
+        if source.copied().unwrap_or_default() != PoseSource::Odometry
+            || max_shift < SLAM_GRAPH_RERENDER_PX
+        {
+            continue;
+        }
+        let latest = graph.keyframes.last().unwrap();
+        matcher.map_to_odom = latest.pose * latest.odom.inverse();
+        matcher.estimate = latest.pose;
+        tree.map_to_odom = matcher.map_to_odom;
+        render_grid(&mut grid, &graph, base_to_laser, &config);
+        metrics.map_rebuilds += 1;
+        info!(
+            "[SLAM] Graph optimized; map shifted by up to {:.0}px",
+            max_shift
+        );
+    }
+}
+
+/// Far enough (or turned enough) from the last keyframe for a new one
+fn is_new_keyframe(graph: &PoseGraph, pose: Pose2d) -> bool {
+    let Some(last) = graph.keyframes.last() else {
+        return true;
+    };
+    last.pose.translation.distance(pose.translation) >= SLAM_KEYFRAME_DIST_PX
+        || wrap_angle(pose.yaw - last.pose.yaw).abs() >= SLAM_KEYFRAME_TURN_DEG.to_radians()
+}
+
+/// Matches keyframe `index` against a local map around the nearest older
+/// keyframe. Recent keyframes are skipped: the front end already tied those
+/// together.
+fn find_loop_closure(
+    graph: &PoseGraph,
+    index: usize,
+    resolution: f32,
+    config: &LidarConfig,
+) -> Option<GraphEdge> {
+    let limit = index.checked_sub(SLAM_LOOP_MIN_GAP)?;
+    let keyframe = &graph.keyframes[index];
+    let (candidate, distance) = graph.keyframes[..limit]
+        .iter()
+        .enumerate()
+        .map(|(i, older)| {
+            (
+                i,
+                older.pose.translation.distance(keyframe.pose.translation),
+            )
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+    if distance > SLAM_LOOP_RADIUS_PX {
+        return None;
+    }
+
+    let base_to_laser = config.base_to_laser();
+    let points = scan_points(&keyframe.scan, base_to_laser);
+    if points.len() < SLAM_MATCH_MIN_POINTS {
+        return None;
+    }
+
+    // Local map of the candidate and its neighbours
+    let mut submap = OccupancyGrid::new(resolution, graph.keyframes[candidate].pose.translation);
+    let first = candidate.saturating_sub(SLAM_LOOP_SUBMAP_HALF_WIDTH);
+    let last = (candidate + SLAM_LOOP_SUBMAP_HALF_WIDTH).min(limit - 1);
+    for older in &graph.keyframes[first..=last] {
+        integrate_scan(&mut submap, older.pose * base_to_laser, &older.scan, config);
+    }
+
+    let (pose, score) = match_scan(&submap, &points, keyframe.pose, &LOOP_WINDOW);
+    (score >= SLAM_LOOP_MIN_SCORE).then(|| GraphEdge {
+        from: candidate,
+        to: index,
+        measurement: graph.keyframes[candidate].pose.inverse() * pose,
+        kind: EdgeKind::LoopClosure,
+    })
+}
+
+/// Rebuilds the grid from every keyframe at its graph pose. Keyframes from
+/// before the last reset contribute their walls only, so re-rendering does
+/// not undo auto-nav's reset. Hazards marked by the bumper / cliff sensors
+/// are dropped.
+fn render_grid(
+    grid: &mut OccupancyGrid,
+    graph: &PoseGraph,
+    base_to_laser: Pose2d,
+    config: &LidarConfig,
+) {
+    grid.clear();
+    let (before_reset, after_reset) = graph.keyframes.split_at(graph.free_from);
+    for keyframe in before_reset {
+        integrate_scan(grid, keyframe.pose * base_to_laser, &keyframe.scan, config);
+    }
+    grid.clear_free();
+    for keyframe in after_reset {
+        integrate_scan(grid, keyframe.pose * base_to_laser, &keyframe.scan, config);
+    }
+}
+
+/// Gauss-Newton over all keyframe poses, the first one held fixed. Each
+/// linear step is solved by block-Jacobi preconditioned conjugate gradients,
+/// applying the sparse system edge by edge.
+fn optimize(graph: &mut PoseGraph) {
+    let n = graph.keyframes.len();
+    if n < 2 {
+        return;
+    }
+    let information = |kind: EdgeKind| {
+        let (sigma_px, sigma_deg) = match kind {
+            EdgeKind::Sequential => (SLAM_ODOM_EDGE_SIGMA_PX, SLAM_ODOM_EDGE_SIGMA_DEG),
+            EdgeKind::LoopClosure => (SLAM_LOOP_EDGE_SIGMA_PX, SLAM_LOOP_EDGE_SIGMA_DEG),
+        };
+        let sigma_rad = sigma_deg.to_radians();
+        Mat3::from_diagonal(Vec3::new(
+            1.0 / (sigma_px * sigma_px),
+            1.0 / (sigma_px * sigma_px),
+            1.0 / (sigma_rad * sigma_rad),
+        ))
+    };
+
+    for _ in 0..SLAM_GRAPH_ITERATIONS {
+        let mut gradient = vec![Vec3::ZERO; n];
+        let mut diagonal = vec![Mat3::ZERO; n];
+        let mut terms = Vec::with_capacity(graph.edges.len());
+        for edge in &graph.edges {
+            let (error, a, b) = linearize(
+                graph.keyframes[edge.from].pose,
+                graph.keyframes[edge.to].pose,
+                edge.measurement,
+            );
+            let omega = information(edge.kind);
+            gradient[edge.from] += a.transpose() * omega * error;
+            gradient[edge.to] += b.transpose() * omega * error;
+            diagonal[edge.from] += a.transpose() * omega * a;
+            diagonal[edge.to] += b.transpose() * omega * b;
+            terms.push((edge.from, edge.to, a, b, omega));
+        }
+
+        // H · v, with the anchor's rows and columns dropped
+        let apply = |v: &[Vec3]| {
+            let mut out = vec![Vec3::ZERO; n];
+            for &(i, j, a, b, omega) in &terms {
+                let r = omega * (a * v[i] + b * v[j]);
+                out[i] += a.transpose() * r;
+                out[j] += b.transpose() * r;
+            }
+            out[0] = Vec3::ZERO;
+            out
+        };
+        let preconditioner: Vec<Mat3> = diagonal
+            .iter()
+            .enumerate()
+            .map(|(i, block)| {
+                if i == 0 || block.determinant().abs() < f32::EPSILON {
+                    Mat3::ZERO
+                } else {
+                    block.inverse()
+                }
+            })
+            .collect();
+        let dot = |x: &[Vec3], y: &[Vec3]| x.iter().zip(y).map(|(x, y)| x.dot(*y)).sum::<f32>();
+
+        let mut step = vec![Vec3::ZERO; n];
+        let mut residual: Vec<Vec3> = gradient.iter().map(|g| -*g).collect();
+        residual[0] = Vec3::ZERO;
+        let mut z: Vec<Vec3> = residual
+            .iter()
+            .zip(&preconditioner)
+            .map(|(r, m)| *m * *r)
+            .collect();
+        let mut direction = z.clone();
+        let mut rz = dot(&residual, &z);
+        let tolerance = rz * 1e-10;
+        for _ in 0..SLAM_GRAPH_CG_ITERATIONS {
+            if rz <= tolerance {
+                break;
+            }
+            let h_direction = apply(&direction);
+            let curvature = dot(&direction, &h_direction);
+            if curvature <= 0.0 {
+                break;
+            }
+            let alpha = rz / curvature;
+            for i in 0..n {
+                step[i] += direction[i] * alpha;
+                residual[i] -= h_direction[i] * alpha;
+            }
+            z = residual
+                .iter()
+                .zip(&preconditioner)
+                .map(|(r, m)| *m * *r)
+                .collect();
+            let rz_next = dot(&residual, &z);
+            let beta = rz_next / rz;
+            rz = rz_next;
+            for i in 0..n {
+                direction[i] = z[i] + direction[i] * beta;
+            }
+        }
+
+        let mut largest = 0.0_f32;
+        for (keyframe, delta) in graph.keyframes.iter_mut().zip(&step) {
+            keyframe.pose = Pose2d::new(
+                keyframe.pose.translation + delta.truncate(),
+                wrap_angle(keyframe.pose.yaw + delta.z),
+            );
+            largest = largest.max(delta.truncate().length());
+        }
+        if largest < 0.01 {
+            break;
+        }
+    }
+}
+
+/// Error of one edge, `(xi⁻¹ · xj) ⊖ z`, and its Jacobians with respect to
+/// `xi` and `xj` (columns: x, y, yaw)
+fn linearize(xi: Pose2d, xj: Pose2d, z: Pose2d) -> (Vec3, Mat3, Mat3) {
+    let (sin, cos) = xi.yaw.sin_cos();
+    // Rᵢᵀ and its derivative with respect to the yaw of xi
+    let ri_t = Mat2::from_cols(Vec2::new(cos, -sin), Vec2::new(sin, cos));
+    let d_ri_t = Mat2::from_cols(Vec2::new(-sin, -cos), Vec2::new(cos, -sin));
+    let rz_t = Mat2::from_angle(-z.yaw);
+
+    let delta = xj.translation - xi.translation;
+    let translation_error = rz_t * (ri_t * delta - z.translation);
+    let error = Vec3::new(
+        translation_error.x,
+        translation_error.y,
+        wrap_angle(xj.yaw - xi.yaw - z.yaw),
+    );
+
+    let rotation = rz_t * ri_t;
+    let d_yaw = rz_t * d_ri_t * delta;
+    let a = Mat3::from_cols(
+        (-rotation.x_axis).extend(0.0),
+        (-rotation.y_axis).extend(0.0),
+        Vec3::new(d_yaw.x, d_yaw.y, -1.0),
+    );
+    let b = Mat3::from_cols(
+        rotation.x_axis.extend(0.0),
+        rotation.y_axis.extend(0.0),
+        Vec3::Z,
+    );
+    (error, a, b)
+}
+
+/// Keyframes in orange, sequential edges in grey, loop closures in magenta
+pub fn pose_graph_debug_draw_system(query: Query<&PoseGraph>, mut gizmos: Gizmos) {
+    for graph in query.iter() {
+        for edge in &graph.edges {
+            let color = match edge.kind {
+                EdgeKind::Sequential => Color::rgba(0.7, 0.7, 0.7, 0.6),
+                EdgeKind::LoopClosure => Color::FUCHSIA,
+            };
+            gizmos.line_2d(
+                graph.keyframes[edge.from].pose.translation,
+                graph.keyframes[edge.to].pose.translation,
+                color,
+            );
+        }
+        for keyframe in &graph.keyframes {
+            gizmos.circle_2d(keyframe.pose.translation, 3.0, Color::ORANGE);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use bevy::prelude::*;
+
+use super::slam_constants::*;
+use crate::components::frames::{wrap_angle, Pose2d, TransformTree};
+use crate::components::lidar::{LaserScan, LidarConfig};
+use crate::components::occupancy_grid::{CellState, OccupancyGrid};
+use crate::components::odometry::{Odometry, OdometryHistory, PoseSource};
+use crate::systems::robot::occupancy_grid::integrate_scan;
+
+/// Scan-to-map matching state for one robot (the SLAM front end).
+///
+/// Each `LaserScan` is aligned with the robot's own occupancy grid, starting
+/// from where odometry says the robot was; the offset found corrects
+/// odometry before the hits are integrated into the grid.
+#[derive(Component, Debug, Clone, Default)]
+pub struct ScanMatcher {
+    /// Corrected pose at the last scan (map frame)
+    pub estimate: Pose2d,
+    /// The correction found so far: `map_to_odom * odom pose = estimate`
+    pub map_to_odom: Pose2d,
+    /// Fraction of the last scan's returns that landed on mapped walls (0..1)
+    pub score: f32,
+    /// Whether the last scan matched well enough to correct odometry
+    pub matched: bool,
+    /// Odometry over the last `SLAM_ODOM_HISTORY_SECS`
+    odom_history: OdometryHistory,
+}
+
+/// A scan aligned with the map, one per `LaserScan`
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ScanMatchPose {
+    pub entity: Entity,
+    /// Sim time of the scan
+    pub stamp: f32,
+    /// Odometry pose at the scan
+    pub odom: Pose2d,
+    /// Corrected pose (the odometry prediction if the match was rejected)
+    pub pose: Pose2d,
+    pub score: f32,
+    pub matched: bool,
+}
+
+/// Matches each new `LaserScan` against the occupancy grid built so far,
+/// then integrates it into the grid at the corrected pose.
+///
+/// Robots with a `ScanMatcher` are mapped a whole scan at a time here rather
+/// than hit by hit in `update_occupancy_grid_system` (otherwise each scan
+/// would already be in the map it is matched against). With
+/// `PoseSource::Odometry` the map → odom correction is also written into the
+/// transform tree for navigation. Scans that fit the map poorly (early on,
+/// or in unmapped space) are mapped at the odometry prediction.
+#[allow(clippy::type_complexity)]
+pub fn scan_match_system(
+    time: Res<Time>,
+    mut scans: EventReader<LaserScan>,
+    mut poses: EventWriter<ScanMatchPose>,
+    mut query: Query<(
+        Entity,
+        &Odometry,
+        Option<&PoseSource>,
+        Option<&LidarConfig>,
+        &mut OccupancyGrid,
+        &mut ScanMatcher,
+        &mut TransformTree,
+    )>,
+) {
+    let scans: Vec<&LaserScan> = scans.read().collect();
+    let now = time.elapsed_seconds();
+
+    for (entity, odometry, source, config, mut grid, mut matcher, mut tree) in query.iter_mut() {
+        let Some(odom_pose) = odometry.pose else {
+            continue;
+        };
+        matcher
+            .odom_history
+            .record(now, odom_pose, SLAM_ODOM_HISTORY_SECS);
+
+        let on_odometry = source.copied().unwrap_or_default() == PoseSource::Odometry;
+        let config = config.copied().unwrap_or_default();
+        let base_to_laser = config.base_to_laser();
+        for scan in scans.iter().filter(|scan| scan.entity == entity) {
+            // Deskewed scans are taken from where the revolution started
+            let odom_at_scan = matcher.odom_history.at(scan.stamp).unwrap_or(odom_pose);
+            let predicted = matcher.map_to_odom * odom_at_scan;
+            let points = scan_points(scan, base_to_laser);
+
+            let (pose, score) = if points.len() >= SLAM_MATCH_MIN_POINTS {
+                match_scan(&grid, &points, predicted, &FRONT_END_WINDOW)
+            } else {
+                (predicted, 0.0)
+            };
+            let matched = score >= SLAM_MATCH_MIN_SCORE;
+
+            matcher.score = score;
+            matcher.matched = matched;
+            matcher.estimate = if matched { pose } else { predicted };
+            if matched {
+                matcher.map_to_odom = pose * odom_at_scan.inverse();
+            }
+            // On ground truth the matcher only observes; the map stays true
+            let mapped_from = if on_odometry {
+                matcher.estimate
+            } else {
+                scan.start_pose
+            };
+            integrate_scan(&mut grid, mapped_from * base_to_laser, scan, &config);
+
+            poses.send(ScanMatchPose {
+                entity,
+                stamp: scan.stamp,
+                odom: odom_at_scan,
+                pose: matcher.estimate,
+                score,
+                matched,
+            });
+        }
+
+        if on_odometry {
+            tree.map_to_odom = matcher.map_to_odom;
+        }
+    }
+}
+
+/// The scan's returns as points in the base frame
+pub fn scan_points(scan: &LaserScan, base_to_laser: Pose2d) -> Vec<Vec2> {
+    scan.ranges
+        .iter()
+        .enumerate()
+        .filter(|(_, range)| **range >= scan.range_min && **range < scan.range_max)
+        .map(|(i, range)| {
+            let angle = scan.angle_min + i as f32 * scan.angle_increment;
+            base_to_laser.transform_point(Vec2::from_angle(angle) * *range)
+        })
+        .collect()
+}
+
+/// Where, and how finely, `match_scan` looks around the prediction
+#[derive(Debug, Clone, Copy)]
+pub struct SearchWindow {
+    /// ± translation (px) and yaw (deg) searched
+    pub search_px: f32,
+    pub search_deg: f32,
+    /// Coarse lattice steps
+    pub step_px: f32,
+    pub step_deg: f32,
+    /// Gaussian prior around the prediction
+    pub prior_sigma_px: f32,
+    pub prior_sigma_deg: f32,
+}
+
+/// The front end's window: the drift of one scan, held close to odometry
+pub const FRONT_END_WINDOW: SearchWindow = SearchWindow {
+    search_px: SLAM_MATCH_SEARCH_PX,
+    search_deg: SLAM_MATCH_SEARCH_DEG,
+    step_px: SLAM_MATCH_STEP_PX,
+    step_deg: SLAM_MATCH_STEP_DEG,
+    prior_sigma_px: SLAM_MATCH_PRIOR_SIGMA_PX,
+    prior_sigma_deg: SLAM_MATCH_PRIOR_SIGMA_DEG,
+};
+
+/// Correlative search around `predicted` for the base pose that lays the
+/// scan points onto solid cells: a coarse pass over the whole window, then
+/// finer passes around the best so far (`SLAM_MATCH_REFINEMENTS`, each a
+/// quarter of the last step). Returns the pose and its hit fraction.
+pub fn match_scan(
+    grid: &OccupancyGrid,
+    points: &[Vec2],
+    predicted: Pose2d,
+    window: &SearchWindow,
+) -> (Pose2d, f32) {
+    let prior_sigma = (window.prior_sigma_px, window.prior_sigma_deg.to_radians());
+    let mut extent = (window.search_px, window.search_deg.to_radians());
+    let mut step = (window.step_px, window.step_deg.to_radians());
+    let mut best = search(
+        grid,
+        points,
+        predicted,
+        prior_sigma,
+        predicted,
+        extent,
+        step,
+    );
+    for _ in 0..SLAM_MATCH_REFINEMENTS {
+        extent = step;
+        step = (step.0 * 0.25, step.1 * 0.25);
+        best = search(grid, points, predicted, prior_sigma, best.0, extent, step);
+    }
+    best
+}
+
+/// Scores every pose on a (translation, yaw) lattice around `centre`.
+///
+/// Candidates are ranked by hit fraction times a Gaussian prior around the
+/// prediction, so featureless stretches (a straight corridor) don't slide
+/// the robot along.
+fn search(
+    grid: &OccupancyGrid,
+    points: &[Vec2],
+    prior: Pose2d,
+    (prior_sigma_px, prior_sigma_rad): (f32, f32),
+    centre: Pose2d,
+    (window_px, window_rad): (f32, f32),
+    (step_px, step_rad): (f32, f32),
+) -> (Pose2d, f32) {
+    let steps_xy = (window_px / step_px).round() as i32;
+    let steps_yaw = (window_rad / step_rad).round() as i32;
+    let two_sigma_sq_px = 2.0 * prior_sigma_px * prior_sigma_px;
+    let two_sigma_sq_yaw = 2.0 * prior_sigma_rad * prior_sigma_rad;
+
+    let mut best = (centre, f32::NEG_INFINITY, 0.0);
+    for r in -steps_yaw..=steps_yaw {
+        let yaw = centre.yaw + r as f32 * step_rad;
+        let rotation = Vec2::from_angle(yaw);
+        let rotated: Vec<Vec2> = points.iter().map(|point| rotation.rotate(*point)).collect();
+        let yaw_offset = wrap_angle(yaw - prior.yaw);
+
+        for iy in -steps_xy..=steps_xy {
+            for ix in -steps_xy..=steps_xy {
+                let translation = centre.translation + Vec2::new(ix as f32, iy as f32) * step_px;
+                let hits = rotated
+                    .iter()
+                    .map(|point| hit_likelihood(grid, translation + *point))
+                    .sum::<f32>()
+                    / points.len() as f32;
+                let offset = translation.distance_squared(prior.translation);
+                let prior_weight =
+                    (-offset / two_sigma_sq_px - yaw_offset * yaw_offset / two_sigma_sq_yaw).exp();
+
+                let score = hits * prior_weight;
+                if score > best.1 {
+                    best = (Pose2d::new(translation, yaw), score, hits);
+                }
+            }
+        }
+    }
+    (best.0, best.2)
+}
+
+/// 1 for a point on a solid cell's centre, falling off with the distance to
+/// the nearest solid cell among its neighbours (0 if there is none)
+fn hit_likelihood(grid: &OccupancyGrid, point: Vec2) -> f32 {
+    let Some(cell) = grid.world_to_cell(point) else {
+        return 0.0;
+    };
+    let mut nearest_sq = f32::INFINITY;
+    for dy in -1..=1 {
+        for dx in -1..=1 {
+            let neighbour = cell + IVec2::new(dx, dy);
+            if grid.state_at(neighbour) == CellState::Solid {
+                nearest_sq = nearest_sq.min(grid.cell_to_world(neighbour).distance_squared(point));
+            }
+        }
+    }
+    (-nearest_sq / (2.0 * SLAM_MATCH_SIGMA_PX * SLAM_MATCH_SIGMA_PX)).exp()
+}
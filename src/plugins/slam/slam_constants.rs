@@ -0,0 +1,82 @@
+// ==========================
+// Scan matching (SLAM front end)
+// ==========================
+
+// Search window around the odometry prediction: ± translation (px) and yaw (deg)
+pub const SLAM_MATCH_SEARCH_PX: f32 = 24.0;
+pub const SLAM_MATCH_SEARCH_DEG: f32 = 6.0;
+
+// Coarse search steps, then refinement passes at a quarter of the last step
+// (the finest yaw step must stay below the heading drift of one scan)
+pub const SLAM_MATCH_STEP_PX: f32 = 4.0;
+pub const SLAM_MATCH_STEP_DEG: f32 = 1.5;
+pub const SLAM_MATCH_REFINEMENTS: usize = 2;
+
+// How far a scan point may sit from a solid cell centre and still count as a hit
+pub const SLAM_MATCH_SIGMA_PX: f32 = 6.0;
+
+// Odometry prior: candidates are scored down by how far they stray from it
+pub const SLAM_MATCH_PRIOR_SIGMA_PX: f32 = 20.0;
+pub const SLAM_MATCH_PRIOR_SIGMA_DEG: f32 = 5.0;
+
+// A match needs this many returns, landing on mapped walls this well (0..1),
+// otherwise the scan is mapped at the odometry prediction
+pub const SLAM_MATCH_MIN_POINTS: usize = 10;
+pub const SLAM_MATCH_MIN_SCORE: f32 = 0.4;
+
+// Odometry poses kept to look up where the robot was when a scan started
+pub const SLAM_ODOM_HISTORY_SECS: f32 = 2.0;
+
+// ==========================
+// Pose graph (SLAM back end)
+// ==========================
+
+// A new keyframe every this much travel (px) or turn (deg) since the last one
+pub const SLAM_KEYFRAME_DIST_PX: f32 = 40.0;
+pub const SLAM_KEYFRAME_TURN_DEG: f32 = 20.0;
+
+// Loop closure candidates: keyframes at least this many keyframes back,
+// within this distance of the new one
+pub const SLAM_LOOP_MIN_GAP: usize = 15;
+pub const SLAM_LOOP_RADIUS_PX: f32 = 80.0;
+
+// Keyframes either side of the candidate rendered into its local map
+pub const SLAM_LOOP_SUBMAP_HALF_WIDTH: usize = 2;
+
+// Loop closure search window and prior around the graph's prediction
+pub const SLAM_LOOP_SEARCH_PX: f32 = 48.0;
+pub const SLAM_LOOP_SEARCH_DEG: f32 = 12.0;
+pub const SLAM_LOOP_STEP_PX: f32 = 6.0;
+pub const SLAM_LOOP_STEP_DEG: f32 = 3.0;
+pub const SLAM_LOOP_PRIOR_SIGMA_PX: f32 = 60.0;
+pub const SLAM_LOOP_PRIOR_SIGMA_DEG: f32 = 15.0;
+
+// A closure must lay this fraction of the returns on the candidate's walls
+pub const SLAM_LOOP_MIN_SCORE: f32 = 0.7;
+
+// Edge standard deviations: between consecutive keyframes, and loop closures
+pub const SLAM_ODOM_EDGE_SIGMA_PX: f32 = 4.0;
+pub const SLAM_ODOM_EDGE_SIGMA_DEG: f32 = 1.5;
+pub const SLAM_LOOP_EDGE_SIGMA_PX: f32 = 3.0;
+pub const SLAM_LOOP_EDGE_SIGMA_DEG: f32 = 1.0;
+
+// Gauss-Newton iterations per optimization (each solved by conjugate gradients)
+pub const SLAM_GRAPH_ITERATIONS: usize = 10;
+pub const SLAM_GRAPH_CG_ITERATIONS: usize = 200;
+
+// The grid is re-rendered once any keyframe moves further than this (px)
+pub const SLAM_GRAPH_RERENDER_PX: f32 = 2.0;
+
+// ==========================
+// Landmark EKF SLAM
+// ==========================
+
+// Motion noise as random walks: the variance grows with distance driven and
+// angle turned (std-dev after 1 m / 1 rad), wide enough to cover odometry bias
+pub const SLAM_EKF_DRIVE_SIGMA_PX_PER_SQRT_M: f32 = 3.0;
+pub const SLAM_EKF_HEADING_SIGMA_DEG_PER_SQRT_M: f32 = 2.0;
+pub const SLAM_EKF_TURN_SIGMA_DEG_PER_SQRT_RAD: f32 = 3.0;
+
+// Sightings whose innovation is this unlikely (squared Mahalanobis distance,
+// chi-square with 2 dof at 99.9%) are rejected
+pub const SLAM_EKF_GATE: f32 = 13.8;
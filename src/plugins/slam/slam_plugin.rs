@@ -0,0 +1,98 @@
+use super::ekf_slam::{ekf_slam_debug_draw_system, ekf_slam_system};
+use super::pose_graph::{pose_graph_debug_draw_system, pose_graph_system};
+use super::scan_matcher::{scan_match_system, ScanMatchPose};
+use crate::app::{RobotSet, SimMode};
+use crate::systems::robot::landmark_detector::landmark_detector_system;
+use crate::systems::robot::occupancy_grid::update_occupancy_grid_system;
+use bevy::prelude::*;
+
+// ┌────────────────────────────────────────────────────────────────────────────┐
+// │                              SLAM OVERVIEW                                 │
+// └────────────────────────────────────────────────────────────────────────────┘
+//
+// Builds the map and corrects the robot's pose at the same time, with no
+// prior map (compare localization/, which needs one).
+//
+// ▶ `scan_match_system` (scan_matcher.rs) — the front end
+//    - Each `LaserScan` is aligned with the hero's `OccupancyGrid` so far by
+//      a correlative search around the odometry prediction (coarse, then
+//      fine), scored by how many returns land on solid cells.
+//    - A good match updates the map → odom correction in `TransformTree`
+//      (with `PoseSource::Odometry`), then the whole scan is integrated at
+//      the corrected pose. `update_occupancy_grid_system` skips these
+//      robots, so a scan is never matched against itself.
+//    - Scans are deskewed in the odom frame (laser_scan.rs), so a
+//      correction landing mid-revolution doesn't bend them.
+//    - Publishes `ScanMatchPose` per scan.
+//
+// ▶ `pose_graph_system` (pose_graph.rs) — the back end (`Slam::PoseGraph`)
+//    - Keeps a keyframe scan every `SLAM_KEYFRAME_DIST_PX` / `_TURN_DEG`,
+//      chained by the front end's relative poses.
+//    - Loop closure: each keyframe is matched against a local map around
+//      the nearest keyframe from well before; a good fit adds an edge.
+//    - After a closure the graph is optimized (Gauss-Newton, conjugate
+//      gradients), the front end is re-anchored on the corrected latest
+//      keyframe and the `OccupancyGrid` is re-rendered from all keyframes,
+//      so the map shifts under the planner. Auto-nav's grid resets are
+//      honoured (`OccupancyGridReset`).
+//
+// ▶ `pose_graph_debug_draw_system` (windowed)
+//    - Keyframes in orange, sequential edges grey, loop closures magenta.
+//
+// ▶ `ekf_slam_system` (ekf_slam.rs) — landmark SLAM (`Slam::Ekf`)
+//    - A second family, for comparison: one EKF over the robot pose and the
+//      position of every collectible seen by the `LandmarkDetector`
+//      (range / bearing, identity known).
+//    - Predicts with each odometry step, corrects per sighting; with
+//      `PoseSource::Odometry` the estimate becomes the map → odom
+//      correction, and the grid is then mapped as usual from the tree.
+//    - Collectibles picked up stay in the state but are never seen again.
+//    - `ekf_slam_debug_draw_system` (windowed): 2σ ellipses, robot yellow,
+//      landmarks cyan.
+//
+// The mapped-with pose is compared with ground truth every step in
+// `SimMetrics` (pose error, RMSE), along with loop closures, map rebuilds and
+// the EKF's landmark position error.
+//
+// Only robots carrying a `ScanMatcher` or an `EkfSlam` run SLAM (`HeroSensors::slam`).
+
+/// Which SLAM method the hero runs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Slam {
+    #[default]
+    None,
+    /// Scan-to-map matching against the occupancy grid
+    ScanMatching,
+    /// Scan matching plus a pose-graph back end with loop closure
+    PoseGraph,
+    /// Landmark EKF over the collectibles (no scan matching)
+    Ekf,
+}
+
+pub struct SlamPlugin;
+
+impl Plugin for SlamPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ScanMatchPose>()
+            .add_systems(
+                FixedUpdate,
+                (scan_match_system, pose_graph_system)
+                    .chain()
+                    .before(update_occupancy_grid_system)
+                    .in_set(RobotSet::Map),
+            )
+            .add_systems(
+                FixedUpdate,
+                ekf_slam_system
+                    .after(landmark_detector_system)
+                    .in_set(RobotSet::Sense),
+            );
+
+        if app.world.resource::<SimMode>().is_windowed() {
+            app.add_systems(
+                Update,
+                (pose_graph_debug_draw_system, ekf_slam_debug_draw_system),
+            );
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use bevy::prelude::*;
+
+use crate::plugins::sim::sim_seed::fnv1a;
+use crate::systems::level::LevelMask;
+use crate::systems::level_descriptor::WallColliders;
+
+/// Bump whenever the cache layout or collider generation changes.
+/// (v1 was the original headerless list of rectangles; v3 added `generator`.)
+pub const COLLISION_CACHE_VERSION: u32 = 3;
+
+const HEADER_PREFIX: &str = "# pick-e collision cache";
+
+/// Whether `spawn_level` may reuse a valid cache file
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CollisionCacheMode {
+    /// Use the cache when its header matches, regenerate otherwise
+    #[default]
+    Use,
+    /// Always regenerate and overwrite (the `--rebuild-caches` tool)
+    Rebuild,
+}
+
+/// First line of a cache file; a cache is only used when every field matches
+/// the level it is being loaded for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CollisionCacheHeader {
+    pub version: u32,
+    /// Size of the collision source image, in image pixels
+    pub image_size: UVec2,
+    pub downscale: usize,
+    /// FNV-1a over the classified mask tiles
+    pub mask_hash: u64,
+    /// `WallColliders::cache_key` of the generator that produced the body
+    pub generator: String,
+}
+
+/// Cache body: wall geometry in mask tile coordinates
+pub enum CachedWalls {
+    /// `(x, y, w, h)` per cuboid
+    Rectangles(Vec<(usize, usize, usize, usize)>),
+    /// Closed outlines, one per line
+    Contours(Vec<Vec<Vec2>>),
+}
+
+impl CachedWalls {
+    /// Number of colliders this spawns
+    pub fn len(&self) -> usize {
+        match self {
+            CachedWalls::Rectangles(rects) => rects.len(),
+            CachedWalls::Contours(contours) => contours.len(),
+        }
+    }
+}
+
+impl CollisionCacheHeader {
+    pub fn for_mask(mask: &LevelMask, downscale: usize, colliders: &WallColliders) -> Self {
+        let mut bytes = Vec::with_capacity(mask.width * mask.height + 16);
+        bytes.extend_from_slice(&(mask.width as u64).to_le_bytes());
+        bytes.extend_from_slice(&(mask.height as u64).to_le_bytes());
+        for row in &mask.classes {
+            bytes.extend(row.iter().map(|class| *class as u8));
+        }
+
+        Self {
+            version: COLLISION_CACHE_VERSION,
+            image_size: mask.source_size,
+            downscale,
+            mask_hash: fnv1a(&bytes),
+            generator: colliders.cache_key(),
+        }
+    }
+
+    pub fn header_line(&self) -> String {
+        format!(
+            "{HEADER_PREFIX} version={} image={}x{} downscale={} hash={:016x} generator={}",
+            self.version,
+            self.image_size.x,
+            self.image_size.y,
+            self.downscale,
+            self.mask_hash,
+            self.generator
+        )
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields = line.strip_prefix(HEADER_PREFIX)?;
+        let value_of = |key: &str| {
+            fields
+                .split_whitespace()
+                .find_map(|kv| kv.strip_prefix(key)?.strip_prefix('='))
+        };
+
+        let (w, h) = value_of("image")?.split_once('x')?;
+        Some(Self {
+            version: value_of("version")?.parse().ok()?,
+            image_size: UVec2::new(w.parse().ok()?, h.parse().ok()?),
+            downscale: value_of("downscale")?.parse().ok()?,
+            mask_hash: u64::from_str_radix(value_of("hash")?, 16).ok()?,
+            generator: value_of("generator")?.to_string(),
+        })
+    }
+}
+
+/// The cached walls, if `text` is a valid cache for `expected`
+pub fn parse_collision_cache(text: &str, expected: &CollisionCacheHeader) -> Option<CachedWalls> {
+    let mut lines = text.lines();
+
+    let Some(header) = lines.next().and_then(CollisionCacheHeader::parse) else {
+        warn!("Collision cache has no valid v{COLLISION_CACHE_VERSION} header");
+        return None;
+    };
+    if header != *expected {
+        warn!(
+            "Collision cache is stale: found [{}], expected [{}]",
+            header.header_line(),
+            expected.header_line()
+        );
+        return None;
+    }
+
+    if header.generator == WallColliders::Rectangles.cache_key() {
+        lines
+            .map(|line| {
+                let parts: Vec<&str> = line.trim().split(',').collect();
+                if parts.len() != 4 {
+                    warn!("Bad line in cache: {line}");
+                    return None;
+                }
+                let x = parts[0].parse::<usize>().ok()?;
+                let y = parts[1].parse::<usize>().ok()?;
+                let w = parts[2].parse::<usize>().ok()?;
+                let h = parts[3].parse::<usize>().ok()?;
+                Some((x, y, w, h))
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(CachedWalls::Rectangles)
+    } else {
+        lines
+            .map(|line| {
+                line.split_whitespace()
+                    .map(|point| {
+                        let (x, y) = point.split_once(',')?;
+                        Some(Vec2::new(x.parse().ok()?, y.parse().ok()?))
+                    })
+                    .collect::<Option<Vec<_>>>()
+                    .filter(|points| points.len() >= 2)
+                    .or_else(|| {
+                        warn!("Bad line in cache: {line}");
+                        None
+                    })
+            })
+            .collect::<Option<Vec<_>>>()
+            .map(CachedWalls::Contours)
+    }
+}
+
+pub fn format_collision_cache(header: &CollisionCacheHeader, walls: &CachedWalls) -> String {
+    let mut lines = vec![header.header_line()];
+    match walls {
+        CachedWalls::Rectangles(rects) => {
+            lines.extend(rects.iter().map(|(x, y, w, h)| format!("{x},{y},{w},{h}")));
+        }
+        CachedWalls::Contours(contours) => {
+            lines.extend(contours.iter().map(|points| {
+                points
+                    .iter()
+                    .map(|p| format!("{},{}", p.x, p.y))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }));
+        }
+    }
+    lines.join("\n")
+}
@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use crate::systems::level::LevelMask;
+
+/// Contours enclosing less than this many tiles are dropped (cf. the 1×1
+/// fragments the rectangle merger skips)
+const MIN_CONTOUR_AREA_TILES: f32 = 1.0;
+
+/// Traces closed outlines around the wall tiles of `mask` with marching squares
+/// and simplifies them (Ramer–Douglas–Peucker, `tolerance` in tiles).
+///
+/// Points are in tile coordinates: (0, 0) is the centre of tile (0, 0), Y points
+/// down. Every vertex lies on a multiple of half a tile.
+pub fn trace_wall_contours(mask: &LevelMask, tolerance: f32) -> Vec<Vec<Vec2>> {
+    // Samples are tile centres, padded by one free sample on every side so
+    // that every contour closes.
+    let (sw, sh) = (mask.width + 2, mask.height + 2);
+    let sample = |sx: usize, sy: usize| -> bool {
+        sx > 0 && sy > 0 && sx <= mask.width && sy <= mask.height && mask.is_wall(sx - 1, sy - 1)
+    };
+
+    // Segment endpoints are edge midpoints, in doubled sample coordinates
+    let mut segments: Vec<(IVec2, IVec2)> = Vec::new();
+    for cy in 0..sh - 1 {
+        for cx in 0..sw - 1 {
+            let case = (sample(cx, cy) as u8) << 3
+                | (sample(cx + 1, cy) as u8) << 2
+                | (sample(cx + 1, cy + 1) as u8) << 1
+                | sample(cx, cy + 1) as u8;
+
+            let (x, y) = (2 * cx as i32, 2 * cy as i32);
+            let top = IVec2::new(x + 1, y);
+            let right = IVec2::new(x + 2, y + 1);
+            let bottom = IVec2::new(x + 1, y + 2);
+            let left = IVec2::new(x, y + 1);
+
+            match case {
+                1 | 14 => segments.push((left, bottom)),
+                2 | 13 => segments.push((bottom, right)),
+                3 | 12 => segments.push((left, right)),
+                4 | 11 => segments.push((top, right)),
+                6 | 9 => segments.push((top, bottom)),
+                7 | 8 => segments.push((left, top)),
+                // Saddles: keep diagonal walls apart
+                5 => {
+                    segments.push((left, bottom));
+                    segments.push((top, right));
+                }
+                10 => {
+                    segments.push((left, top));
+                    segments.push((bottom, right));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    chain_loops(&segments)
+        .into_iter()
+        .map(|points| {
+            points
+                .into_iter()
+                .map(|p| p.as_vec2() / 2.0 - Vec2::ONE)
+                .collect::<Vec<_>>()
+        })
+        .filter(|loop_| polygon_area(loop_).abs() >= MIN_CONTOUR_AREA_TILES)
+        .map(|loop_| simplify_closed(&loop_, tolerance))
+        .collect()
+}
+
+/// Joins segments into closed loops (every endpoint is shared by exactly two)
+fn chain_loops(segments: &[(IVec2, IVec2)]) -> Vec<Vec<IVec2>> {
+    let mut neighbours: HashMap<IVec2, Vec<usize>> = HashMap::new();
+    for (i, (a, b)) in segments.iter().enumerate() {
+        neighbours.entry(*a).or_default().push(i);
+        neighbours.entry(*b).or_default().push(i);
+    }
+
+    let mut used = vec![false; segments.len()];
+    let mut loops = Vec::new();
+
+    for start in 0..segments.len() {
+        if used[start] {
+            continue;
+        }
+        used[start] = true;
+        let (first, mut current) = segments[start];
+        let mut points = vec![first];
+
+        while current != first {
+            points.push(current);
+            let Some(&next) = neighbours[&current].iter().find(|&&s| !used[s]) else {
+                break;
+            };
+            used[next] = true;
+            let (a, b) = segments[next];
+            current = if a == current { b } else { a };
+        }
+        loops.push(points);
+    }
+
+    loops
+}
+
+fn polygon_area(points: &[Vec2]) -> f32 {
+    let n = points.len();
+    (0..n)
+        .map(|i| points[i].perp_dot(points[(i + 1) % n]))
+        .sum::<f32>()
+        / 2.0
+}
+
+/// RDP on a closed loop: split at the vertex furthest from the first one and
+/// simplify both halves as open polylines.
+fn simplify_closed(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if points.len() < 4 {
+        return points.to_vec();
+    }
+    let far = (1..points.len())
+        .max_by(|&a, &b| {
+            let da = points[a].distance_squared(points[0]);
+            let db = points[b].distance_squared(points[0]);
+            da.total_cmp(&db)
+        })
+        .unwrap_or(points.len() / 2);
+
+    let mut first_half = simplify_open(&points[..=far], tolerance);
+    let mut second: Vec<Vec2> = points[far..].to_vec();
+    second.push(points[0]);
+    let second_half = simplify_open(&second, tolerance);
+
+    // Drop the shared endpoints (far, and the repeated first point)
+    first_half.extend_from_slice(&second_half[1..second_half.len() - 1]);
+    first_half
+}
+
+fn simplify_open(points: &[Vec2], tolerance: f32) -> Vec<Vec2> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let (a, b) = (points[0], points[points.len() - 1]);
+
+    let (index, dist) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1, distance_to_segment(*p, a, b)))
+        .fold(
+            (0, 0.0),
+            |best, cur| if cur.1 > best.1 { cur } else { best },
+        );
+
+    if dist <= tolerance {
+        return vec![a, b];
+    }
+
+    let mut left = simplify_open(&points[..=index], tolerance);
+    let right = simplify_open(&points[index..], tolerance);
+    left.pop();
+    left.extend(right);
+    left
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let len2 = ab.length_squared();
+    if len2 == 0.0 {
+        return p.distance(a);
+    }
+    let t = ((p - a).dot(ab) / len2).clamp(0.0, 1.0);
+    p.distance(a + ab * t)
+}
@@ -2,65 +2,168 @@ use bevy::prelude::*;
 use bevy::render::texture::Image;
 use bevy_rapier2d::prelude::*;
 
-// Embed the cache file as a string on WASM
+use crate::app::SimMode;
+use crate::bundles::hero::HeroController;
+use crate::systems::collectibles::CollectibleFloodState;
+use crate::systems::collision_cache::{
+    format_collision_cache, parse_collision_cache, CachedWalls, CollisionCacheHeader,
+    CollisionCacheMode,
+};
+use crate::systems::contours::trace_wall_contours;
+use crate::systems::level_descriptor::{
+    CollisionSource, LevelDescriptor, LevelSelection, MaskClass, MaskMapping, SurfaceMaterial,
+    WallColliders,
+};
+
+// Embed the default level's cache file as a string on WASM
 #[cfg(target_arch = "wasm32")]
 const COLLISION_CACHE: &str = include_str!("../../assets/collision-cache.txt");
+#[cfg(target_arch = "wasm32")]
+const EMBEDDED_CACHE_PATH: &str = "collision-cache.txt";
 const DOWNSCALE_FACTOR: usize = 4;
 const DEBUG_DRAW_COLLISIONS: bool = false;
 
+/// Marker for static wall colliders generated from the level mask
+#[derive(Component)]
+pub struct MergedWall;
+
 #[derive(Resource)]
 pub struct LevelAssets {
-    pub background: Handle<Image>,
+    pub descriptor: Handle<LevelDescriptor>,
     pub spawned: bool,
 }
 
-pub fn setup_level_loading(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let background = asset_server.load("textures/map-beauty.png");
+/// The level in play: its descriptor plus the wall mask built from it.
+/// Inserted by `spawn_level` once every asset it needs has loaded.
+#[derive(Resource)]
+pub struct ActiveLevel {
+    pub descriptor: LevelDescriptor,
+    pub mask: LevelMask,
+}
+
+pub fn setup_level_loading(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    selection: Res<LevelSelection>,
+) {
+    let descriptor = asset_server.load(selection.0.clone());
 
     commands.insert_resource(LevelAssets {
-        background,
+        descriptor,
         spawned: false,
     });
 
-    info!("Level textures requested...");
+    info!("Level {} requested...", selection.0);
+}
+
+/// Run condition: colliders and collectibles are in place, so the robot may start.
+pub fn level_ready(
+    level_assets: Option<Res<LevelAssets>>,
+    flood_state: Res<CollectibleFloodState>,
+) -> bool {
+    level_assets.is_some_and(|l| l.spawned) && flood_state.has_spawned
 }
 
 pub fn spawn_level(
     mut commands: Commands,
     images: Res<Assets<Image>>,
+    descriptors: Res<Assets<LevelDescriptor>>,
     mut level_assets: ResMut<LevelAssets>,
+    mode: Res<SimMode>,
+    cache_mode: Res<CollisionCacheMode>,
+    mut heroes: Query<(&mut Transform, &mut Velocity), With<HeroController>>,
 ) {
     if level_assets.spawned {
         return;
     }
-    let Some(beauty_texture) = images.get(&level_assets.background) else {
+    let Some(descriptor) = descriptors.get(&level_assets.descriptor) else {
+        debug!("Waiting for level descriptor to load...");
+        return;
+    };
+    let Some(beauty_texture) = images.get(&descriptor.background) else {
         debug!("Waiting for beauty texture to load...");
         return;
     };
 
+    let mask = match &descriptor.collision {
+        CollisionSource::Background => {
+            LevelMask::from_beauty(beauty_texture, descriptor.world_scale)
+        }
+        CollisionSource::Mask { image, mapping } => {
+            let Some(mask_image) = images.get(image) else {
+                debug!("Waiting for collision mask to load...");
+                return;
+            };
+            if mask_image.size() != beauty_texture.size() {
+                warn!(
+                    "Collision mask is {:?} but background is {:?} — walls follow the mask",
+                    mask_image.size(),
+                    beauty_texture.size()
+                );
+            }
+            LevelMask::from_mask(mask_image, descriptor.world_scale, mapping)
+        }
+    };
+
     level_assets.spawned = true;
-    info!("Level textures loaded! Attempting to spawn...");
+    info!(
+        "Level \"{}\" textures loaded! Attempting to spawn...",
+        descriptor.name
+    );
 
-    commands.spawn((
-        SpriteBundle {
-            texture: level_assets.background.clone(),
-            transform: Transform {
-                translation: Vec3::new(0.0, 0.0, -1.0),
-                scale: Vec3::ONE,
-                ..default()
-            },
-            sprite: Sprite {
-                anchor: bevy::sprite::Anchor::Center,
+    if mode.is_windowed() {
+        commands.spawn((
+            SpriteBundle {
+                texture: descriptor.background.clone(),
+                transform: Transform {
+                    translation: Vec3::new(0.0, 0.0, -1.0),
+                    scale: Vec3::splat(descriptor.world_scale),
+                    ..default()
+                },
+                sprite: Sprite {
+                    anchor: bevy::sprite::Anchor::Center,
+                    ..default()
+                },
                 ..default()
             },
-            ..default()
-        },
-        Name::new("LevelBackground"),
-    ));
+            Name::new("LevelBackground"),
+        ));
+    }
+
+    // Put the hero on the level's start pose
+    for (mut transform, mut velocity) in heroes.iter_mut() {
+        *transform = descriptor.spawn.transform();
+        *velocity = Velocity::zero();
+    }
+
+    spawn_walls(
+        &mut commands,
+        &mask,
+        &descriptor.colliders,
+        descriptor.collision_cache.as_deref(),
+        *cache_mode,
+    );
+
+    commands.insert_resource(ActiveLevel {
+        descriptor: descriptor.clone(),
+        mask,
+    });
+}
+
+fn spawn_walls(
+    commands: &mut Commands,
+    mask: &LevelMask,
+    colliders: &WallColliders,
+    cache_path: Option<&str>,
+    cache_mode: CollisionCacheMode,
+) {
+    let header = CollisionCacheHeader::for_mask(mask, DOWNSCALE_FACTOR, colliders);
 
     // Try loading from cache first
-    if let Some(text) = try_load_collision_cache("assets/collision-cache.txt") {
-        if try_spawn_from_cache(&mut commands, &text, beauty_texture).is_some() {
+    if cache_mode == CollisionCacheMode::Rebuild {
+        info!("Rebuilding collision cache");
+    } else if let Some(text) = cache_path.and_then(try_load_collision_cache) {
+        if try_spawn_from_cache(commands, &text, mask, &header).is_some() {
             info!("Spawned level from collision cache");
             return;
         } else {
@@ -71,23 +174,31 @@ pub fn spawn_level(
     }
 
     // Fallback: generate from mask
-    let merged_rects = generate_colliders_from_beauty(beauty_texture, &mut commands);
+    let walls = match colliders {
+        WallColliders::Rectangles => {
+            CachedWalls::Rectangles(generate_colliders_from_beauty(mask, commands))
+        }
+        WallColliders::Contours { tolerance } => {
+            let contours = trace_wall_contours(mask, *tolerance);
+            for points in &contours {
+                spawn_contour_collider(commands, points, mask);
+            }
+            CachedWalls::Contours(contours)
+        }
+    };
 
     #[cfg(not(target_arch = "wasm32"))]
-    {
-        let lines: Vec<String> = merged_rects
-            .iter()
-            .map(|(x, y, w, h)| format!("{x},{y},{w},{h}"))
-            .collect();
-        if let Err(e) = std::fs::write("assets/collision-cache.txt", lines.join("\n")) {
+    if let Some(path) = cache_path {
+        let text = format_collision_cache(&header, &walls);
+        if let Err(e) = std::fs::write(format!("assets/{path}"), text) {
             warn!("Failed to write collision cache: {e}");
         }
     }
 
     info!(
         "Spawned {} colliders from mask (tile = {:.2})",
-        merged_rects.len(),
-        DOWNSCALE_FACTOR as f32
+        walls.len(),
+        mask.tile_size
     );
 }
 
@@ -95,39 +206,39 @@ pub fn spawn_level(
 // Helpers
 // -----------------------------------------------------------------------------
 
-fn try_load_collision_cache(_path: &str) -> Option<String> {
+fn try_load_collision_cache(path: &str) -> Option<String> {
     #[cfg(target_arch = "wasm32")]
     {
-        Some(COLLISION_CACHE.to_string())
+        (path == EMBEDDED_CACHE_PATH).then(|| COLLISION_CACHE.to_string())
     }
     #[cfg(not(target_arch = "wasm32"))]
     {
-        std::fs::read_to_string(_path).ok()
+        std::fs::read_to_string(format!("assets/{path}")).ok()
     }
 }
 
-fn try_spawn_from_cache(commands: &mut Commands, text: &str, beauty: &Image) -> Option<()> {
-    let tile_size = DOWNSCALE_FACTOR as f32;
-    let origin_offset = compute_origin_offset(beauty, tile_size);
-
-    let mut num_colliders = 0;
-
-    for line in text.lines() {
-        let parts: Vec<&str> = line.trim().split(',').collect();
-        if parts.len() != 4 {
-            warn!("Bad line in cache: {line}");
-            return None;
+fn try_spawn_from_cache(
+    commands: &mut Commands,
+    text: &str,
+    mask: &LevelMask,
+    header: &CollisionCacheHeader,
+) -> Option<()> {
+    let walls = parse_collision_cache(text, header)?;
+
+    match &walls {
+        CachedWalls::Rectangles(rects) => {
+            for &(x, y, w, h) in rects {
+                spawn_collider(commands, x, y, w, h, mask.tile_size, mask.origin_offset);
+            }
+        }
+        CachedWalls::Contours(contours) => {
+            for points in contours {
+                spawn_contour_collider(commands, points, mask);
+            }
         }
-        let x = parts[0].parse::<usize>().ok()?;
-        let y = parts[1].parse::<usize>().ok()?;
-        let w = parts[2].parse::<usize>().ok()?;
-        let h = parts[3].parse::<usize>().ok()?;
-
-        spawn_collider(commands, x, y, w, h, tile_size, origin_offset);
-        num_colliders += 1;
     }
 
-    info!("Spawned {num_colliders} colliders from cache");
+    info!("Spawned {} colliders from cache", walls.len());
     Some(())
 }
 
@@ -155,40 +266,189 @@ pub fn is_clearly_blue(r: u8, g: u8, b: u8, a: u8) -> bool {
     bf > rf && bf > gf && bf >= BLUE_DOMINANCE_RATIO * avg_rg
 }
 
-pub fn generate_colliders_from_beauty(
-    beauty: &Image,
-    commands: &mut Commands,
-) -> Vec<(usize, usize, usize, usize)> {
-    let full_w = beauty.size().x as usize;
-    let full_h = beauty.size().y as usize;
-    let stride = 4;
-    let data = &beauty.data;
+/// Per-tile classification of the level (walls, free floor, no-go areas),
+/// downscaled by `DOWNSCALE_FACTOR` from the collision source image.
+///
+/// Tile (0, 0) is the top-left of the image; world Y points up.
+#[derive(Clone)]
+pub struct LevelMask {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: f32,
+    /// World position of tile (0, 0)'s centre
+    pub origin_offset: Vec2,
+    /// Size of the image the tiles were sampled from
+    pub source_size: UVec2,
+    /// `classes[y][x]`
+    pub classes: Vec<Vec<MaskClass>>,
+    /// `materials[y][x]`: what LiDAR sees on wall tiles
+    pub materials: Vec<Vec<SurfaceMaterial>>,
+}
 
-    let w = full_w / DOWNSCALE_FACTOR;
-    let h = full_h / DOWNSCALE_FACTOR;
+impl LevelMask {
+    /// Walls are the clearly-blue pixels of the beauty texture.
+    /// `world_scale` = world pixels per image pixel.
+    pub fn from_beauty(image: &Image, world_scale: f32) -> Self {
+        Self::from_image(image, world_scale, |[r, g, b, a]| {
+            let class = if is_clearly_blue(r, g, b, a) {
+                MaskClass::Wall
+            } else {
+                MaskClass::Free
+            };
+            (class, SurfaceMaterial::Matte)
+        })
+    }
 
-    let mut solid = vec![vec![false; w]; h];
+    /// Classes and materials come from a dedicated mask image via its colour table
+    pub fn from_mask(image: &Image, world_scale: f32, mapping: &MaskMapping) -> Self {
+        Self::from_image(image, world_scale, |rgba| mapping.classify(rgba))
+    }
 
-    for y in 0..h {
-        for x in 0..w {
-            let orig_x = x * DOWNSCALE_FACTOR;
-            let orig_y = y * DOWNSCALE_FACTOR;
-            let idx = (orig_y * full_w + orig_x) * stride;
+    fn from_image(
+        image: &Image,
+        world_scale: f32,
+        classify: impl Fn([u8; 4]) -> (MaskClass, SurfaceMaterial),
+    ) -> Self {
+        let full_w = image.size().x as usize;
+        let full_h = image.size().y as usize;
+        let stride = 4;
+        let data = &image.data;
+
+        let width = full_w / DOWNSCALE_FACTOR;
+        let height = full_h / DOWNSCALE_FACTOR;
+
+        let tiles: Vec<Vec<(MaskClass, SurfaceMaterial)>> = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        let orig_x = x * DOWNSCALE_FACTOR;
+                        let orig_y = y * DOWNSCALE_FACTOR;
+                        let idx = (orig_y * full_w + orig_x) * stride;
+                        classify([data[idx], data[idx + 1], data[idx + 2], data[idx + 3]])
+                    })
+                    .collect()
+            })
+            .collect();
+        let classes = tiles
+            .iter()
+            .map(|row| row.iter().map(|tile| tile.0).collect())
+            .collect();
+        let materials = tiles
+            .iter()
+            .map(|row| row.iter().map(|tile| tile.1).collect())
+            .collect();
+
+        let tile_size = DOWNSCALE_FACTOR as f32 * world_scale;
+
+        Self {
+            width,
+            height,
+            tile_size,
+            origin_offset: compute_origin_offset(image, tile_size),
+            source_size: image.size(),
+            classes,
+            materials,
+        }
+    }
+
+    /// Has a collider
+    pub fn is_wall(&self, x: usize, y: usize) -> bool {
+        self.classes[y][x] == MaskClass::Wall
+    }
+
+    /// Can't be entered (wall or no-go)
+    pub fn is_blocked(&self, x: usize, y: usize) -> bool {
+        self.classes[y][x] != MaskClass::Free
+    }
+
+    /// Tile whose square contains `pos`, if inside the mask
+    pub fn world_to_tile(&self, pos: Vec2) -> Option<(usize, usize)> {
+        let x = ((pos.x - self.origin_offset.x) / self.tile_size).round();
+        let y = ((self.origin_offset.y - pos.y) / self.tile_size).round();
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return None;
+        }
+        Some((x as usize, y as usize))
+    }
+
+    /// Whether `pos` lies over a drop (see `MaskClass::Drop`)
+    pub fn is_drop_at(&self, pos: Vec2) -> bool {
+        self.world_to_tile(pos)
+            .is_some_and(|(x, y)| self.classes[y][x] == MaskClass::Drop)
+    }
+
+    /// Surface material of the wall at `pos`; `None` off walls
+    pub fn wall_material_at(&self, pos: Vec2) -> Option<SurfaceMaterial> {
+        let (x, y) = self.world_to_tile(pos)?;
+        self.is_wall(x, y).then(|| self.materials[y][x])
+    }
+
+    pub fn tile_to_world(&self, x: usize, y: usize) -> Vec2 {
+        self.tile_point_to_world(Vec2::new(x as f32, y as f32))
+    }
+
+    /// Fractional tile coordinates (Y down) → world position
+    pub fn tile_point_to_world(&self, p: Vec2) -> Vec2 {
+        Vec2::new(
+            self.origin_offset.x + p.x * self.tile_size,
+            self.origin_offset.y - p.y * self.tile_size,
+        )
+    }
+
+    /// First tile (in scan order) whose centre lies within one tile of `pos`
+    pub fn find_tile_near(&self, pos: Vec2) -> Option<(usize, usize)> {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let world = self.tile_to_world(x, y);
+                if (world.x - pos.x).abs() < self.tile_size
+                    && (world.y - pos.y).abs() < self.tile_size
+                {
+                    return Some((x, y));
+                }
+            }
+        }
+        None
+    }
 
-            let r = data[idx];
-            let g = data[idx + 1];
-            let b = data[idx + 2];
-            let a = data[idx + 3];
+    /// 4-connected flood over free tiles from `start`; `reachable[y][x]`
+    pub fn reachable_from(&self, start: (usize, usize)) -> Vec<Vec<bool>> {
+        let mut reachable = vec![vec![false; self.width]; self.height];
+        let mut queue = vec![start];
 
-            solid[y][x] = is_clearly_blue(r, g, b, a);
+        while let Some((x, y)) = queue.pop() {
+            if x >= self.width || y >= self.height || reachable[y][x] || self.is_blocked(x, y) {
+                continue;
+            }
+            reachable[y][x] = true;
+
+            if x > 0 {
+                queue.push((x - 1, y));
+            }
+            queue.push((x + 1, y));
+            if y > 0 {
+                queue.push((x, y - 1));
+            }
+            queue.push((x, y + 1));
         }
+
+        reachable
     }
+}
+
+pub fn generate_colliders_from_beauty(
+    mask: &LevelMask,
+    commands: &mut Commands,
+) -> Vec<(usize, usize, usize, usize)> {
+    let (w, h) = (mask.width, mask.height);
+    let solid: Vec<Vec<bool>> = (0..h)
+        .map(|y| (0..w).map(|x| mask.is_wall(x, y)).collect())
+        .collect();
 
     let mut visited = vec![vec![false; w]; h];
     let mut merged = Vec::new();
 
-    let tile_size = DOWNSCALE_FACTOR as f32;
-    let origin_offset = compute_origin_offset(beauty, tile_size);
+    let tile_size = mask.tile_size;
+    let origin_offset = mask.origin_offset;
 
     for y in 0..h {
         for x in 0..w {
@@ -260,6 +520,7 @@ fn spawn_collider(
         Collider::cuboid(half_w, half_h),
         Transform::from_translation(world_pos),
         GlobalTransform::default(),
+        MergedWall,
         Name::new("MergedWall"),
     ));
 
@@ -289,3 +550,21 @@ fn spawn_collider(
         ));
     }
 }
+
+/// Closed polyline collider along a traced wall outline (points in tile coordinates)
+fn spawn_contour_collider(commands: &mut Commands, points: &[Vec2], mask: &LevelMask) {
+    let mut vertices: Vec<Vec2> = points
+        .iter()
+        .map(|p| mask.tile_point_to_world(*p))
+        .collect();
+    vertices.push(vertices[0]);
+
+    commands.spawn((
+        RigidBody::Fixed,
+        Collider::polyline(vertices, None),
+        Transform::from_translation(Vec3::new(0.0, 0.0, 0.5)),
+        GlobalTransform::default(),
+        MergedWall,
+        Name::new("WallContour"),
+    ));
+}
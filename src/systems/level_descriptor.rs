@@ -0,0 +1,288 @@
+use bevy::asset::io::Reader;
+use bevy::asset::{ron, AssetLoader, AsyncReadExt, BoxedFuture, LoadContext};
+use bevy::prelude::*;
+use serde::Deserialize;
+
+/// Level loaded when nothing else is selected (path relative to `assets/`)
+pub const DEFAULT_LEVEL: &str = "levels/default.level.ron";
+
+/// Which level descriptor to load at startup (overridable from the command line)
+#[derive(Resource, Clone, Debug)]
+pub struct LevelSelection(pub String);
+
+impl Default for LevelSelection {
+    fn default() -> Self {
+        Self(DEFAULT_LEVEL.to_string())
+    }
+}
+
+/// A test environment, described by a `*.level.ron` file under `assets/levels/`.
+///
+/// ```ron
+/// (
+///     name: "House",
+///     background: "textures/map-beauty.png",
+///     collision: Background, // or Mask((image: "textures/map-mask.png", classes: [...]))
+///     colliders: Rectangles, // or Contours(tolerance: 0.75)
+///     collision_cache: Some("collision-cache.txt"),
+///     spawn: (x: 0.0, y: 200.0, yaw_deg: 0.0),
+///     collectibles: (grid_size: 100.0, jitter_fraction: 0.3, radius: 12.0),
+///     world_scale: 1.0,
+/// )
+/// ```
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct LevelDescriptor {
+    pub name: String,
+    /// Beauty texture drawn behind everything
+    pub background: Handle<Image>,
+    pub collision: CollisionSource,
+    pub colliders: WallColliders,
+    /// Collider cache file (relative to `assets/`); `None` = always regenerate
+    pub collision_cache: Option<String>,
+    pub spawn: SpawnPose,
+    pub collectibles: CollectiblePlacement,
+    /// World pixels per source-image pixel
+    pub world_scale: f32,
+}
+
+/// Where wall geometry comes from
+#[derive(Clone, Debug)]
+pub enum CollisionSource {
+    /// Clearly-blue pixels of the background image are walls
+    Background,
+    /// A dedicated mask image, classified by colour
+    Mask {
+        image: Handle<Image>,
+        mapping: MaskMapping,
+    },
+}
+
+/// How wall tiles are turned into Rapier colliders
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum WallColliders {
+    /// Greedy merge into axis-aligned cuboids
+    #[default]
+    Rectangles,
+    /// Marching-squares outlines as polylines, simplified to within
+    /// `tolerance` tiles
+    Contours { tolerance: f32 },
+}
+
+impl WallColliders {
+    /// Identifies the generator (and its settings) in the collision cache header
+    pub fn cache_key(&self) -> String {
+        match self {
+            WallColliders::Rectangles => "rectangles".to_string(),
+            WallColliders::Contours { tolerance } => format!("contours@{tolerance}"),
+        }
+    }
+}
+
+/// What a mask pixel means to the simulation
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MaskClass {
+    /// Open floor
+    #[default]
+    Free,
+    /// Solid wall: gets colliders and blocks LiDAR
+    Wall,
+    /// Virtual barrier: no collider, but never reachable (no collectibles, not
+    /// counted for coverage)
+    NoGo,
+    /// A drop (stairs, ledge): no collider and invisible to LiDAR, only the
+    /// cliff sensors notice it. Never reachable, like `NoGo`
+    Drop,
+}
+
+/// How a wall surface treats LiDAR beams
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SurfaceMaterial {
+    /// Diffuse: a clean return
+    #[default]
+    Matte,
+    /// Transparent: the beam passes through and no return comes back from it
+    Glass,
+    /// Specular: the beam bounces, so the sensor reports a phantom behind it
+    Mirror,
+    /// Dark / absorbing: a weak return, lost beyond short range
+    Absorbing,
+}
+
+/// One mask colour and the class it stands for
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct MaskColour {
+    pub rgba: (u8, u8, u8, u8),
+    pub class: MaskClass,
+    /// Only meaningful for `Wall`
+    #[serde(default)]
+    pub material: SurfaceMaterial,
+}
+
+/// Colour → class table for a mask image.
+///
+/// Each pixel takes the class of the nearest listed colour (RGBA distance), so
+/// anti-aliased edges snap to one side; pixels further than `tolerance` from
+/// every entry fall back to `default`.
+#[derive(Clone, Debug)]
+pub struct MaskMapping {
+    pub classes: Vec<MaskColour>,
+    pub tolerance: f32,
+    pub default: MaskClass,
+}
+
+fn default_mask_tolerance() -> f32 {
+    128.0
+}
+
+impl MaskMapping {
+    /// Class and surface material of one pixel
+    pub fn classify(&self, [r, g, b, a]: [u8; 4]) -> (MaskClass, SurfaceMaterial) {
+        let mut best = (
+            self.tolerance * self.tolerance,
+            (self.default, SurfaceMaterial::default()),
+        );
+        for entry in &self.classes {
+            let (er, eg, eb, ea) = entry.rgba;
+            let d2 = [(r, er), (g, eg), (b, eb), (a, ea)]
+                .iter()
+                .map(|&(p, e)| (p as f32 - e as f32).powi(2))
+                .sum::<f32>();
+            if d2 <= best.0 {
+                best = (d2, (entry.class, entry.material));
+            }
+        }
+        best.1
+    }
+}
+
+/// Hero start pose in world coordinates
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct SpawnPose {
+    pub x: f32,
+    pub y: f32,
+    /// Heading in degrees, counter-clockwise from +X
+    #[serde(default)]
+    pub yaw_deg: f32,
+}
+
+impl SpawnPose {
+    pub fn position(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+
+    pub fn transform(&self) -> Transform {
+        Transform::from_translation(self.position().extend(0.0))
+            .with_rotation(Quat::from_rotation_z(self.yaw_deg.to_radians()))
+    }
+}
+
+/// Settings for the collectible flood-fill
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct CollectiblePlacement {
+    /// One collectible per grid square of this size (world pixels)
+    pub grid_size: f32,
+    /// Random offset from the square's centre, as a fraction of `grid_size`
+    pub jitter_fraction: f32,
+    /// Visual radius, also the clearance kept from walls
+    pub radius: f32,
+}
+
+impl Default for CollectiblePlacement {
+    fn default() -> Self {
+        Self {
+            grid_size: 100.0,
+            jitter_fraction: 0.3,
+            radius: 12.0,
+        }
+    }
+}
+
+/// On-disk form: asset paths are plain strings until the loader resolves them
+#[derive(Deserialize)]
+struct LevelDescriptorFile {
+    #[serde(default)]
+    name: String,
+    background: String,
+    #[serde(default)]
+    collision: CollisionSourceFile,
+    #[serde(default)]
+    colliders: WallColliders,
+    #[serde(default)]
+    collision_cache: Option<String>,
+    spawn: SpawnPose,
+    #[serde(default)]
+    collectibles: CollectiblePlacement,
+    #[serde(default = "default_world_scale")]
+    world_scale: f32,
+}
+
+#[derive(Deserialize, Default)]
+enum CollisionSourceFile {
+    #[default]
+    Background,
+    Mask(MaskSourceFile),
+}
+
+#[derive(Deserialize)]
+struct MaskSourceFile {
+    image: String,
+    classes: Vec<MaskColour>,
+    #[serde(default = "default_mask_tolerance")]
+    tolerance: f32,
+    #[serde(default)]
+    default: MaskClass,
+}
+
+fn default_world_scale() -> f32 {
+    1.0
+}
+
+#[derive(Default)]
+pub struct LevelDescriptorLoader;
+
+impl AssetLoader for LevelDescriptorLoader {
+    type Asset = LevelDescriptor;
+    type Settings = ();
+    type Error = Box<dyn std::error::Error + Send + Sync>;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await?;
+            let file: LevelDescriptorFile = ron::de::from_bytes(&bytes)?;
+
+            let collision = match file.collision {
+                CollisionSourceFile::Background => CollisionSource::Background,
+                CollisionSourceFile::Mask(mask) => CollisionSource::Mask {
+                    image: load_context.load(mask.image),
+                    mapping: MaskMapping {
+                        classes: mask.classes,
+                        tolerance: mask.tolerance,
+                        default: mask.default,
+                    },
+                },
+            };
+
+            Ok(LevelDescriptor {
+                name: file.name,
+                background: load_context.load(file.background),
+                collision,
+                colliders: file.colliders,
+                collision_cache: file.collision_cache,
+                spawn: file.spawn,
+                collectibles: file.collectibles,
+                world_scale: file.world_scale,
+            })
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
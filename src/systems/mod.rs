@@ -1,4 +1,7 @@
 pub mod collectibles;
+pub mod collision_cache;
+pub mod contours;
 pub mod level;
+pub mod level_descriptor;
 pub mod robot;
 pub mod startup;
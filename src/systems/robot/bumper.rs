@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use bevy_rapier2d::rapier::geometry::CollisionEventFlags;
+
+use crate::components::bumper::{BumpEvent, BumperSensor};
+
+/// Contacts further than this from straight ahead miss the bumper (degrees)
+const BUMPER_HALF_ARC_DEG: f32 = 90.0;
+/// Contacts this close to straight ahead press both switches (degrees)
+const BUMPER_CENTRE_DEG: f32 = 10.0;
+
+/// Tracks what touches each bumper-equipped body (Rapier collision events) and
+/// works out from the contact points which switches are pressed. Sends a
+/// `BumpEvent` when a switch closes.
+///
+/// Runs per fixed step after the physics writeback, so no event is missed.
+pub fn bumper_sensor_system(
+    mut collision_events: EventReader<CollisionEvent>,
+    rapier_context: Res<RapierContext>,
+    mut query: Query<(Entity, &mut BumperSensor)>,
+    mut bumps: EventWriter<BumpEvent>,
+) {
+    let events: Vec<CollisionEvent> = collision_events.read().cloned().collect();
+
+    for (entity, mut bumper) in query.iter_mut() {
+        for event in &events {
+            match *event {
+                CollisionEvent::Started(e1, e2, flags) => {
+                    if flags.contains(CollisionEventFlags::SENSOR) {
+                        continue;
+                    }
+                    let other = if e1 == entity {
+                        e2
+                    } else if e2 == entity {
+                        e1
+                    } else {
+                        continue;
+                    };
+                    if !bumper.touching.contains(&other) {
+                        bumper.touching.push(other);
+                    }
+                }
+                CollisionEvent::Stopped(e1, e2, _) => {
+                    bumper
+                        .touching
+                        .retain(|&e| !((e1 == entity && e == e2) || (e2 == entity && e == e1)));
+                }
+            }
+        }
+
+        let (mut left, mut right) = (false, false);
+        let mut bump_point = Vec2::ZERO;
+        for &other in &bumper.touching {
+            let Some(pair) = rapier_context.contact_pair(entity, other) else {
+                continue;
+            };
+            if !pair.has_any_active_contacts() {
+                continue;
+            }
+            let ours_first = pair.collider1() == entity;
+            for manifold in pair.manifolds() {
+                for contact in manifold.points() {
+                    // The collider sits on the body, so its local frame is
+                    // base_link (raw Rapier units: scale back to world pixels)
+                    let point = if ours_first {
+                        contact.local_p1()
+                    } else {
+                        contact.local_p2()
+                    } * rapier_context.physics_scale();
+                    let angle = point.y.atan2(point.x).to_degrees();
+                    if angle.abs() > BUMPER_HALF_ARC_DEG {
+                        continue;
+                    }
+                    left |= angle >= -BUMPER_CENTRE_DEG;
+                    right |= angle <= BUMPER_CENTRE_DEG;
+                    bump_point = point;
+                }
+            }
+        }
+
+        let newly_left = left && !bumper.left_pressed;
+        let newly_right = right && !bumper.right_pressed;
+        if newly_left || newly_right {
+            bumps.send(BumpEvent {
+                entity,
+                left: newly_left,
+                right: newly_right,
+                local_point: bump_point,
+            });
+        }
+        bumper.left_pressed = left;
+        bumper.right_pressed = right;
+    }
+}
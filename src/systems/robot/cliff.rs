@@ -0,0 +1,33 @@
+use bevy::prelude::*;
+
+use crate::components::cliff::{CliffEvent, CliffSensors};
+use crate::components::frames::Pose2d;
+use crate::systems::level::ActiveLevel;
+
+/// Checks each cliff sensor against the level mask (ground truth) and sends a
+/// `CliffEvent` when one starts seeing a drop.
+pub fn cliff_sensor_system(
+    level: Option<Res<ActiveLevel>>,
+    mut query: Query<(Entity, &GlobalTransform, &mut CliffSensors)>,
+    mut cliffs: EventWriter<CliffEvent>,
+) {
+    let Some(level) = level else {
+        return;
+    };
+
+    for (entity, transform, mut sensors) in query.iter_mut() {
+        let pose = Pose2d::from_global(transform);
+        let sensors = &mut *sensors;
+
+        for (offset, triggered) in sensors.offsets.iter().zip(sensors.triggered.iter_mut()) {
+            let over_drop = level.mask.is_drop_at(pose.transform_point(*offset));
+            if over_drop && !*triggered {
+                cliffs.send(CliffEvent {
+                    entity,
+                    local_point: *offset,
+                });
+            }
+            *triggered = over_drop;
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::*;
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::components::collectible::Collectible;
+use crate::components::frames::{wrap_angle, Pose2d};
+use crate::components::landmark::{LandmarkDetector, LandmarkObservation};
+use crate::plugins::sim::sim_seed::{gaussian, SimSeed};
+
+/// Reports the collectibles each `LandmarkDetector` can see, at its rate.
+///
+/// A landmark is seen when it is within range and field of view and no wall
+/// stands in between (ground truth); range and bearing get Gaussian noise
+/// and some sightings are missed (`detection_prob`).
+pub fn landmark_detector_system(
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    seed: Res<SimSeed>,
+    mut rng: Local<Option<StdRng>>,
+    mut observations: EventWriter<LandmarkObservation>,
+    mut query: Query<(Entity, &GlobalTransform, &mut LandmarkDetector)>,
+    collectibles: Query<(Entity, &GlobalTransform), With<Collectible>>,
+) {
+    let rng = rng.get_or_insert_with(|| seed.rng("landmark_noise"));
+
+    for (entity, transform, mut detector) in query.iter_mut() {
+        detector.since_last += time.delta_seconds();
+        if detector.since_last < 1.0 / detector.rate_hz {
+            continue;
+        }
+        detector.since_last = 0.0;
+
+        let pose = Pose2d::from_global(transform);
+        // Walls only: collectibles (group 2) don't hide each other
+        let filter = QueryFilter::default()
+            .exclude_collider(entity)
+            .groups(CollisionGroups::new(
+                Group::ALL,
+                Group::ALL ^ Group::GROUP_2,
+            ));
+
+        for (landmark, landmark_transform) in collectibles.iter() {
+            let offset = landmark_transform.translation().truncate() - pose.translation;
+            let range = offset.length();
+            let bearing = wrap_angle(offset.y.atan2(offset.x) - pose.yaw);
+            if range > detector.max_range || !detector.in_fov(bearing) {
+                continue;
+            }
+            let occluded = rapier_context
+                .cast_ray(pose.translation, offset / range, range, true, filter)
+                .is_some();
+            if occluded || rng.gen::<f32>() >= detector.detection_prob {
+                continue;
+            }
+
+            observations.send(LandmarkObservation {
+                entity,
+                landmark,
+                range: range + detector.range_sigma_px * gaussian(rng),
+                bearing: wrap_angle(
+                    bearing + detector.bearing_sigma_deg.to_radians() * gaussian(rng),
+                ),
+            });
+        }
+    }
+}
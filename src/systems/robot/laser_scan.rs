@@ -0,0 +1,87 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::components::frames::TransformTree;
+use crate::components::lidar::{LaserScan, LaserScanAccumulator, LidarConfig, LidarEmitter};
+
+/// Assembles each LIDAR's per-step hits into full revolutions and publishes
+/// one `LaserScan` event per revolution.
+pub fn assemble_laser_scan_system(
+    mut query: Query<(
+        Entity,
+        &LidarEmitter,
+        Option<&LidarConfig>,
+        &TransformTree,
+        &mut LaserScanAccumulator,
+    )>,
+    mut scans: EventWriter<LaserScan>,
+) {
+    for (entity, emitter, config, tree, mut accumulator) in query.iter_mut() {
+        let config = config.copied().unwrap_or_default();
+        let beams = (360.0 / config.angle_step_deg).round().max(1.0) as usize;
+        // Deskew in the odom frame: smooth, even if localization moves the map under it
+        let odom_to_laser = tree.odom_to_base * tree.base_to_laser;
+
+        for hit in &emitter.hits {
+            // The mirror passed 360°: the revolution in progress is complete
+            if accumulator
+                .last_angle_deg
+                .is_some_and(|last| hit.angle_deg < last)
+            {
+                if let Some(scan) = accumulator.scan.take() {
+                    scans.send(scan);
+                }
+            }
+            accumulator.last_angle_deg = Some(hit.angle_deg);
+
+            if accumulator.scan.is_none() {
+                accumulator.start_laser_pose = odom_to_laser;
+                accumulator.scan = Some(LaserScan {
+                    entity,
+                    stamp: hit.stamp,
+                    angle_min: 0.0,
+                    angle_increment: TAU / beams as f32,
+                    range_min: config.min_range,
+                    range_max: config.max_range,
+                    ranges: vec![f32::INFINITY; beams],
+                    intensities: vec![0.0; beams],
+                    timestamps: vec![f32::NAN; beams],
+                    start_pose: tree.map_to_base(),
+                    end_pose: tree.map_to_base(),
+                    motion_distorted: config.motion_distortion,
+                });
+            }
+            let start_laser_pose = accumulator.start_laser_pose;
+            let Some(scan) = accumulator.scan.as_mut() else {
+                continue;
+            };
+            scan.end_pose = tree.map_to_base();
+            // No return: leave the bin empty rather than deskew a made-up point
+            if hit.distance >= config.max_range {
+                continue;
+            }
+
+            // Without distortion, re-express the return in the laser frame the
+            // scan started in (deskew); with it, keep the raw reading
+            let (angle, range) = if config.motion_distortion {
+                (hit.angle_deg.to_radians(), hit.distance)
+            } else {
+                let point = start_laser_pose
+                    .inverse()
+                    .transform_point(odom_to_laser.transform_point(hit.local_point()));
+                (point.y.atan2(point.x), point.length().min(config.max_range))
+            };
+
+            let bin = ((angle - scan.angle_min).rem_euclid(TAU) / scan.angle_increment).round()
+                as usize
+                % beams;
+            // Two returns in one bin (only when deskewing): keep the nearer
+            if range < scan.ranges[bin] {
+                scan.ranges[bin] = range;
+                scan.intensities[bin] = hit.intensity;
+                scan.timestamps[bin] = hit.stamp - scan.stamp;
+            }
+        }
+    }
+}
@@ -1,89 +1,224 @@
-use crate::components::lidar::{LidarEmitter, LidarHit, LidarSensor};
+use crate::components::frames::{Pose2d, TransformTree};
+use crate::components::lidar::{LidarConfig, LidarEmitter, LidarHit, LidarNoise, LidarSensor};
 use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
+use rand::rngs::StdRng;
+use rand::Rng;
 
-use crate::constants::{LIDAR_ANGLE_STEP, LIDAR_MAX_RANGE_PX, LIDAR_SPIN_RATE_HZ};
+use crate::plugins::sim::sim_seed::{gaussian, SimSeed};
+use crate::systems::level::{ActiveLevel, LevelMask};
+use crate::systems::level_descriptor::SurfaceMaterial;
 
-#[cfg(debug_assertions)]
-use crate::components::lidar::DebugHitInfo;
+/// Return strength off a matte wall hit head-on
+const MATTE_INTENSITY: f32 = 0.8;
+/// Return strength off an absorbing surface hit head-on
+const ABSORBING_INTENSITY: f32 = 0.1;
+/// Absorbing surfaces return nothing beyond this fraction of max range
+const ABSORBING_MAX_RANGE_FRACTION: f32 = 0.3;
+/// Fraction of the beam surviving each mirror bounce
+const MIRROR_REFLECTANCE: f32 = 0.9;
+/// Bounces / glass panes one beam may pass before it is given up on
+const MAX_SURFACE_INTERACTIONS: usize = 4;
 
 /// Emits a realistic LIDAR scan arc each frame (no accumulation).
+#[allow(clippy::type_complexity)]
 pub fn lidar_sensor_system(
     time: Res<Time>,
     rapier_context: Res<RapierContext>,
-    mut query: Query<(&GlobalTransform, &mut LidarEmitter, Entity), With<LidarSensor>>,
+    level: Option<Res<ActiveLevel>>,
+    seed: Res<SimSeed>,
+    mut rng: Local<Option<StdRng>>,
+    mut query: Query<
+        (
+            &GlobalTransform,
+            &mut LidarEmitter,
+            Option<&LidarConfig>,
+            Option<&LidarNoise>,
+            Entity,
+        ),
+        With<LidarSensor>,
+    >,
 ) {
-    for (transform, mut emitter, entity) in query.iter_mut() {
-        let origin = transform.translation().truncate();
-        let angle_delta = 360.0 * LIDAR_SPIN_RATE_HZ * time.delta_seconds();
+    let rng = rng.get_or_insert_with(|| seed.rng("lidar_noise"));
+    let mask = level.as_ref().map(|level| &level.mask);
+
+    for (transform, mut emitter, config, noise, entity) in query.iter_mut() {
+        let config = config.copied().unwrap_or_default();
+        let noise = noise.copied().unwrap_or_default();
+        // Rays are cast from where the sensor really is (ground truth)
+        let map_to_laser = Pose2d::from_global(transform) * config.base_to_laser();
+        let origin = map_to_laser.translation;
+        let max_range = config.max_range;
+        let angle_delta = 360.0 * config.spin_rate_hz * time.delta_seconds();
 
         let start_angle = emitter.angle_cursor;
         let end_angle = start_angle + angle_delta;
+        // The mirror sweeps `angle_delta` over the step that ends now
+        let step_start_secs = time.elapsed_seconds() - time.delta_seconds();
 
         emitter.hits.clear();
 
         let mut angle = start_angle;
         while angle < end_angle {
-            let angle_rad = angle.to_radians();
+            // `angle` is the mirror position in the sensor frame; beams outside
+            // the FOV arc are blocked by the housing
+            let beam_angle = angle;
+            angle += config.angle_step_deg;
+            if !config.in_fov(beam_angle) {
+                continue;
+            }
+            if noise.dropout_prob > 0.0 && rng.gen::<f32>() < noise.dropout_prob {
+                continue;
+            }
+
+            // The beam actually leaves at a slightly different angle than reported
+            let cast_angle = if noise.angle_jitter_deg > 0.0 {
+                beam_angle + noise.angle_jitter_deg * gaussian(rng)
+            } else {
+                beam_angle
+            };
+            let angle_rad = map_to_laser.yaw + cast_angle.to_radians();
             let dir = Vec2::new(angle_rad.cos(), angle_rad.sin());
 
             use bevy_rapier2d::geometry::{CollisionGroups, Group};
 
-            let ray_result = rapier_context.cast_ray(
-                origin,
-                dir,
-                LIDAR_MAX_RANGE_PX,
-                true,
+            let filter =
                 QueryFilter::default()
                     .exclude_collider(entity)
                     .groups(CollisionGroups::new(
                         Group::ALL,
                         Group::ALL ^ Group::GROUP_2,
-                    )),
-            );
-
-            let distance = match ray_result {
-                Some((_e, toi)) => toi,
-                None => LIDAR_MAX_RANGE_PX,
-            };
-
-            // Always push a hit; only the debug payload is cfg-gated
-            #[cfg(debug_assertions)]
-            {
-                emitter.hits.push(LidarHit {
-                    angle_deg: angle % 360.0,
-                    distance,
-                    debug: DebugHitInfo {
-                        hit_point: origin + dir * distance,
-                    },
-                });
+                    ));
+            let (mut distance, mut intensity) =
+                trace_beam(&rapier_context, filter, mask, origin, dir, max_range);
+            if distance < config.min_range {
+                // Inside the blind zone: the sensor reports nothing
+                continue;
             }
-            #[cfg(not(debug_assertions))]
-            {
-                emitter.hits.push(LidarHit {
-                    angle_deg: angle % 360.0,
-                    distance,
-                });
+            if distance < max_range {
+                if noise.spurious_max_range_prob > 0.0
+                    && rng.gen::<f32>() < noise.spurious_max_range_prob
+                {
+                    distance = max_range;
+                    intensity = 0.0;
+                } else if noise.range_sigma_fraction > 0.0 {
+                    let sigma = noise.range_sigma_fraction * distance;
+                    distance = (distance + sigma * gaussian(rng)).clamp(0.0, max_range);
+                }
             }
 
-            angle += LIDAR_ANGLE_STEP;
+            // Reported in the laser frame, like a real sensor
+            emitter.hits.push(LidarHit {
+                angle_deg: beam_angle.rem_euclid(360.0),
+                distance,
+                intensity,
+                stamp: step_start_secs
+                    + (beam_angle - start_angle) / angle_delta * time.delta_seconds(),
+            });
         }
 
         emitter.angle_cursor = end_angle % 360.0;
     }
 }
 
-/// Debug-draws the rays emitted this frame from each LIDAR
+/// Follows one beam through the level's surfaces: `(distance, intensity)`.
+///
+/// Glass is passed through, mirrors bounce the beam (the reported distance is
+/// the whole path, so the return appears behind the mirror), absorbing
+/// surfaces only answer at short range. No return reads as max range with
+/// zero intensity.
+fn trace_beam(
+    rapier_context: &RapierContext,
+    filter: QueryFilter,
+    mask: Option<&LevelMask>,
+    mut origin: Vec2,
+    mut dir: Vec2,
+    max_range: f32,
+) -> (f32, f32) {
+    let no_return = (max_range, 0.0);
+    let mut travelled = 0.0;
+    let mut strength = 1.0;
+
+    for _ in 0..MAX_SURFACE_INTERACTIONS {
+        let Some((_, hit)) = rapier_context.cast_ray_and_get_normal(
+            origin,
+            dir,
+            max_range - travelled,
+            true,
+            filter,
+        ) else {
+            return no_return;
+        };
+        travelled += hit.toi;
+
+        // A ray starting inside a collider reports a zero normal
+        let normal = hit.normal.try_normalize();
+        let incidence = normal.map_or(1.0, |n| dir.dot(n).abs());
+        let Some(mask) = mask else {
+            return (travelled, MATTE_INTENSITY * incidence);
+        };
+        // Sample half a tile into the surface, not on its edge
+        let inside = hit.point - normal.unwrap_or(-dir) * mask.tile_size * 0.5;
+        let material = mask.wall_material_at(inside).unwrap_or_default();
+
+        match material {
+            SurfaceMaterial::Matte => {
+                return (travelled, strength * MATTE_INTENSITY * incidence);
+            }
+            SurfaceMaterial::Absorbing => {
+                if travelled > ABSORBING_MAX_RANGE_FRACTION * max_range {
+                    return no_return;
+                }
+                return (travelled, strength * ABSORBING_INTENSITY * incidence);
+            }
+            SurfaceMaterial::Mirror => {
+                let Some(normal) = normal else {
+                    return no_return;
+                };
+                dir -= 2.0 * dir.dot(normal) * normal;
+                origin = hit.point + normal * 0.01;
+                strength *= MIRROR_REFLECTANCE;
+            }
+            SurfaceMaterial::Glass => {
+                // March through the pane, then carry on from its far side
+                let step = mask.tile_size * 0.5;
+                let mut point = hit.point + dir * step;
+                travelled += step;
+                while mask.wall_material_at(point) == Some(SurfaceMaterial::Glass) {
+                    if travelled >= max_range {
+                        return no_return;
+                    }
+                    point += dir * step;
+                    travelled += step;
+                }
+                origin = point;
+            }
+        }
+
+        if travelled >= max_range {
+            return no_return;
+        }
+    }
+
+    no_return
+}
+
+/// Debug-draws the rays emitted this frame from each LIDAR, placed in the map
+/// through the robot's transform tree
 pub fn lidar_debug_draw_system(
-    query: Query<(&GlobalTransform, &LidarEmitter), With<LidarSensor>>,
+    query: Query<(&TransformTree, &LidarEmitter), With<LidarSensor>>,
     mut gizmos: Gizmos,
 ) {
     #[cfg(debug_assertions)]
-    for (transform, emitter) in query.iter() {
-        let origin = transform.translation().truncate();
+    for (tree, emitter) in query.iter() {
+        let map_to_laser = tree.map_to_laser();
 
         for hit in &emitter.hits {
-            gizmos.line_2d(origin, hit.debug.hit_point, Color::rgba(1.0, 0.2, 0.0, 0.5));
+            gizmos.line_2d(
+                map_to_laser.translation,
+                map_to_laser.transform_point(hit.local_point()),
+                Color::rgba(1.0, 0.2, 0.0, 0.5),
+            );
         }
     }
 }
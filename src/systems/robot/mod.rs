@@ -1,4 +1,10 @@
+pub mod bumper;
+pub mod cliff;
 pub mod cmd_vel_drive;
 pub mod input_keyboard;
+pub mod landmark_detector;
+pub mod laser_scan;
 pub mod lidar_sensor;
 pub mod occupancy_grid;
+pub mod odometry;
+pub mod transform_tree;
@@ -1,71 +1,182 @@
-use crate::components::lidar::LidarEmitter;
-use crate::components::occupancy_grid::{CellState, OccupancyGrid};
-use crate::constants::OCCUPANCY_ASSUMED_MAX_LIDAR_RANGE_PX;
+use crate::components::bumper::BumpEvent;
+use crate::components::cliff::CliffEvent;
+use crate::components::frames::{Pose2d, TransformTree};
+use crate::components::lidar::{LaserScan, LidarConfig, LidarEmitter};
+use crate::components::occupancy_grid::{CellState, OccupancyGrid, OccupancyGridUpdated};
+use crate::constants::OCCUPANCY_ASSUMED_MAX_LIDAR_RANGE_FRACTION;
+use crate::plugins::slam::scan_matcher::ScanMatcher;
 
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 
-/// Updates the occupancy grid using LIDAR hits (per-entity)
+/// Updates the occupancy grid using LIDAR hits (per-entity).
+///
+/// Hits are in the laser frame; the transform tree places them in the map.
+/// Robots running scan matching are skipped: `scan_match_system` integrates
+/// their whole scans once the pose is corrected.
+#[allow(clippy::type_complexity)]
 pub fn update_occupancy_grid_system(
-    mut query: Query<(&GlobalTransform, &LidarEmitter, &mut OccupancyGrid)>,
+    mut query: Query<
+        (
+            &TransformTree,
+            &LidarEmitter,
+            Option<&LidarConfig>,
+            &mut OccupancyGrid,
+        ),
+        Without<ScanMatcher>,
+    >,
 ) {
-    for (transform, emitter, mut grid) in query.iter_mut() {
-        let origin = transform.translation().truncate();
+    for (tree, emitter, config, mut grid) in query.iter_mut() {
+        let config = config.copied().unwrap_or_default();
+        let map_to_laser = tree.map_to_laser();
+        let assumed_max_range = OCCUPANCY_ASSUMED_MAX_LIDAR_RANGE_FRACTION * config.max_range;
 
         for hit in emitter.hits.iter() {
-            let angle_rad = hit.angle_deg.to_radians();
-            let dir = Vec2::new(angle_rad.cos(), angle_rad.sin());
-            let max_distance = hit.distance;
+            let angle_rad = map_to_laser.yaw + hit.angle_deg.to_radians();
+            integrate_beam(
+                &mut grid,
+                map_to_laser.translation,
+                Vec2::new(angle_rad.cos(), angle_rad.sin()),
+                hit.distance,
+                assumed_max_range,
+            );
+        }
+    }
+}
 
-            // Sample along the beam
-            let steps = (max_distance / grid.resolution).ceil() as usize;
-            for step in 0..=steps {
-                let distance = step as f32 * grid.resolution;
-                let point = origin + dir * distance;
+/// Integrates a whole `LaserScan` taken from `map_to_laser` (the laser pose
+/// at its first beam). Beams without a return clear up to the max range.
+pub fn integrate_scan(
+    grid: &mut OccupancyGrid,
+    map_to_laser: Pose2d,
+    scan: &LaserScan,
+    config: &LidarConfig,
+) {
+    let assumed_max_range = OCCUPANCY_ASSUMED_MAX_LIDAR_RANGE_FRACTION * config.max_range;
+    for (i, range) in scan.ranges.iter().enumerate() {
+        let angle = scan.angle_min + i as f32 * scan.angle_increment;
+        if !config.in_fov(angle.to_degrees()) {
+            continue;
+        }
+        let angle_rad = map_to_laser.yaw + angle;
+        integrate_beam(
+            grid,
+            map_to_laser.translation,
+            Vec2::new(angle_rad.cos(), angle_rad.sin()),
+            range.min(scan.range_max),
+            assumed_max_range,
+        );
+    }
+}
 
-                let grid_x = ((point.x - grid.origin.x) / grid.resolution).floor() as isize;
-                let grid_y = ((point.y - grid.origin.y) / grid.resolution).floor() as isize;
+/// Adds one beam's evidence: a miss for each cell it passes through and,
+/// for a real return, a hit where it ends. Beams without a (trusted) return
+/// clear up to the assumed max range.
+fn integrate_beam(
+    grid: &mut OccupancyGrid,
+    origin: Vec2,
+    dir: Vec2,
+    max_distance: f32,
+    assumed_max_range: f32,
+) {
+    let is_return = max_distance < assumed_max_range;
+    let end = origin + dir * max_distance.min(assumed_max_range);
+    let cells = grid.beam_cells(origin, end);
+    let Some((&end_cell, passed)) = cells.split_last() else {
+        return;
+    };
+    for &cell in passed {
+        grid.observe_miss(cell);
+    }
+    if is_return {
+        grid.observe_hit(end_cell);
+    } else {
+        grid.observe_miss(end_cell);
+    }
+}
 
-                if grid_x < 0 || grid_y < 0 {
-                    continue;
-                }
+/// Reports the cells whose state changed during this step's map update
+/// (`OccupancyGridUpdated`), after every integrator has run
+pub fn publish_occupancy_grid_updates_system(
+    mut updates: EventWriter<OccupancyGridUpdated>,
+    mut query: Query<(Entity, &mut OccupancyGrid)>,
+) {
+    for (entity, mut grid) in query.iter_mut() {
+        if let Some(region) = grid.take_dirty() {
+            updates.send(OccupancyGridUpdated { entity, region });
+        }
+    }
+}
 
-                let (x, y) = (grid_x as usize, grid_y as usize);
+/// Marks what the bumper and cliff sensors found as solid: obstacles LiDAR
+/// missed (glass, black furniture) and drops it cannot see at all
+pub fn mark_hazards_system(
+    mut bumps: EventReader<BumpEvent>,
+    mut cliffs: EventReader<CliffEvent>,
+    mut query: Query<(&TransformTree, &mut OccupancyGrid)>,
+) {
+    // Bumps are nudged outward, past the shell into whatever was hit
+    let bump_points = bumps.read().map(|bump| {
+        (
+            bump.entity,
+            bump.local_point,
+            bump.local_point.normalize_or_zero(),
+        )
+    });
+    let cliff_points = cliffs
+        .read()
+        .map(|cliff| (cliff.entity, cliff.local_point, Vec2::ZERO));
 
-                // Mark final point as solid
-                if step == steps {
-                    if hit.distance < OCCUPANCY_ASSUMED_MAX_LIDAR_RANGE_PX {
-                        grid.set(x, y, CellState::Solid);
-                    }
-                // Else: we don't assume anything — not Solid, not Free
-                } else {
-                    // Only mark as free if not already known to be solid
-                    if grid.get(x, y).unwrap_or(CellState::Unknown) != CellState::Solid {
-                        grid.set(x, y, CellState::Free);
-                    }
-                }
-            }
+    for (entity, local_point, outward) in bump_points.chain(cliff_points) {
+        let Ok((tree, mut grid)) = query.get_mut(entity) else {
+            continue;
+        };
+        let local_point = local_point + outward * grid.resolution * 0.5;
+        let point = tree.map_to_base().transform_point(local_point);
+        if let Some(cell) = grid.world_to_cell(point) {
+            grid.set_cell(cell, CellState::Solid);
         }
     }
 }
 
-/// Draws known cells in the occupancy grid as colored boxes
-pub fn draw_occupancy_grid_system(mut gizmos: Gizmos, query: Query<&OccupancyGrid>) {
+/// Draws known cells in the occupancy grid as colored boxes.
+///
+/// Known cells are cached per grid and refreshed only inside the regions
+/// reported by `OccupancyGridUpdated`, instead of rescanning every cell.
+pub fn draw_occupancy_grid_system(
+    mut gizmos: Gizmos,
+    mut updates: EventReader<OccupancyGridUpdated>,
+    mut known: Local<HashMap<Entity, HashMap<IVec2, CellState>>>,
+    query: Query<&OccupancyGrid>,
+) {
     #[cfg(debug_assertions)]
-    for grid in query.iter() {
-        for y in 0..grid.height {
-            for x in 0..grid.width {
-                let state = grid.get(x, y).unwrap_or(CellState::Unknown);
-                if state == CellState::Unknown {
-                    continue;
+    {
+        for update in updates.read() {
+            let Ok(grid) = query.get(update.entity) else {
+                continue;
+            };
+            let cells = known.entry(update.entity).or_default();
+            let region = update.region;
+            for y in region.min.y..=region.max.y {
+                for x in region.min.x..=region.max.x {
+                    let cell = IVec2::new(x, y);
+                    match grid.state_at(cell) {
+                        CellState::Unknown => cells.remove(&cell),
+                        state => cells.insert(cell, state),
+                    };
                 }
+            }
+        }
 
-                const OCCUPANCY_GRID_Z: f32 = 10.0;
+        const OCCUPANCY_GRID_Z: f32 = 10.0;
 
-                let center = Vec3::new(
-                    grid.origin.x + (x as f32 + 0.5) * grid.resolution,
-                    grid.origin.y + (y as f32 + 0.5) * grid.resolution,
-                    OCCUPANCY_GRID_Z, // Z-layer: push in front of sprites
-                );
+        for (entity, cells) in known.iter() {
+            let Ok(grid) = query.get(*entity) else {
+                continue;
+            };
+            for (cell, state) in cells.iter() {
+                // Z-layer: push in front of sprites
+                let center = grid.cell_to_world(*cell).extend(OCCUPANCY_GRID_Z);
 
                 let color = match state {
                     CellState::Free => Color::rgba(0.1, 1.0, 0.1, 0.1), // green
@@ -0,0 +1,89 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use rand::rngs::StdRng;
+
+use crate::components::frames::Pose2d;
+use crate::components::odometry::{Odometry, WheelOdometry};
+use crate::constants::METERS_PER_PIXEL;
+use crate::plugins::sim::kidnap::Kidnapped;
+use crate::plugins::sim::sim_metrics::SimMetrics;
+use crate::plugins::sim::sim_seed::{gaussian, SimSeed};
+
+/// Dead-reckons each robot from the wheel motion of the last physics step.
+///
+/// The encoders see how far the body rolled forward and turned (sideways skid
+/// is invisible to them); slip and bias from `WheelOdometry` are applied before
+/// integrating. The drift from the true pose is recorded in `SimMetrics`; a
+/// kidnap re-anchors it, so the teleport itself never counts as drift.
+#[allow(clippy::too_many_arguments)]
+pub fn wheel_odometry_system(
+    time: Res<Time>,
+    seed: Res<SimSeed>,
+    mut rng: Local<Option<StdRng>>,
+    mut metrics: ResMut<SimMetrics>,
+    mut last_truth: Local<HashMap<Entity, Pose2d>>,
+    // True pose → where perfect odometry would be (identity until a kidnap)
+    mut drift_reference: Local<HashMap<Entity, Pose2d>>,
+    mut kidnapped: EventReader<Kidnapped>,
+    mut query: Query<(
+        Entity,
+        &GlobalTransform,
+        Option<&WheelOdometry>,
+        &mut Odometry,
+    )>,
+) {
+    let rng = rng.get_or_insert_with(|| seed.rng("odometry_noise"));
+    let dt = time.delta_seconds();
+    let teleported: Vec<Entity> = kidnapped.read().map(|event| event.entity).collect();
+
+    for (entity, transform, noise, mut odometry) in query.iter_mut() {
+        let truth = Pose2d::from_global(transform);
+        let previous = last_truth.insert(entity, truth);
+        // The odom frame is laid down where the robot starts
+        let (Some(mut pose), Some(previous)) = (odometry.pose, previous) else {
+            odometry.pose = Some(truth);
+            continue;
+        };
+        let reference = drift_reference.get(&entity).copied().unwrap_or_default();
+        // Carried off, not driven: the wheels saw nothing, and perfect
+        // odometry would still be where the robot was picked up
+        if teleported.contains(&entity) {
+            drift_reference.insert(entity, reference * previous * truth.inverse());
+            continue;
+        }
+        let noise = noise.copied().unwrap_or_default();
+
+        // What ideal encoders would report: the step seen from the robot's base
+        let step = previous.inverse() * truth;
+        let mut distance = step.translation.x;
+        let mut turn = Vec2::X.angle_between(Vec2::from_angle(step.yaw));
+
+        distance *= 1.0 + noise.scale_bias;
+        if noise.slip_sigma_fraction > 0.0 {
+            distance *= 1.0 + noise.slip_sigma_fraction * gaussian(rng);
+        }
+        if noise.turn_slip_sigma_fraction > 0.0 {
+            turn *= 1.0 + noise.turn_slip_sigma_fraction * gaussian(rng);
+        }
+        turn += noise.yaw_bias_deg_per_m.to_radians() * distance.abs() * METERS_PER_PIXEL;
+
+        // Midpoint integration: drive along the average heading of the step
+        let mid_yaw = pose.yaw + turn * 0.5;
+        pose.translation += Vec2::from_angle(mid_yaw) * distance;
+        pose.yaw += turn;
+
+        odometry.pose = Some(pose);
+        if dt > 0.0 {
+            odometry.linear = distance / dt;
+            odometry.angular = turn / dt;
+        }
+
+        let ideal = reference * truth;
+        let error = pose.translation.distance(ideal.translation);
+        metrics.odometry_error_px = error;
+        metrics.odometry_error_max_px = metrics.odometry_error_max_px.max(error);
+        metrics.odometry_heading_error_deg = Vec2::from_angle(ideal.yaw)
+            .angle_between(Vec2::from_angle(pose.yaw))
+            .to_degrees();
+    }
+}
@@ -0,0 +1,32 @@
+use bevy::prelude::*;
+
+use crate::components::frames::{Pose2d, TransformTree};
+use crate::components::lidar::LidarConfig;
+use crate::components::odometry::{Odometry, PoseSource};
+
+/// Refreshes each robot's transform tree for this step.
+///
+/// With `PoseSource::Odometry`, `odom_to_base` is the wheel-odometry pose and
+/// mapping / planning inherit its drift. Otherwise it is the true pose (as
+/// seen through `map_to_odom`, so the tree still lands on ground truth).
+/// `map_to_odom` is left to whatever localisation sets it to (identity by
+/// default).
+#[allow(clippy::type_complexity)]
+pub fn update_transform_tree_system(
+    mut query: Query<(
+        &GlobalTransform,
+        Option<&LidarConfig>,
+        Option<&PoseSource>,
+        Option<&Odometry>,
+        &mut TransformTree,
+    )>,
+) {
+    for (transform, config, source, odometry, mut tree) in query.iter_mut() {
+        let odometry_pose = odometry.and_then(|odometry| odometry.pose);
+        tree.odom_to_base = match (source.copied().unwrap_or_default(), odometry_pose) {
+            (PoseSource::Odometry, Some(pose)) => pose,
+            _ => tree.map_to_odom.inverse() * Pose2d::from_global(transform),
+        };
+        tree.base_to_laser = config.copied().unwrap_or_default().base_to_laser();
+    }
+}
@@ -1,9 +1,43 @@
+use crate::app::SimMode;
 use crate::bundles::camera::camera_2d_bundle;
-use crate::bundles::hero::hero_bundle;
+use crate::bundles::hero::{hero_bundle, hero_sprite_bundle, HeroSensors};
+use crate::components::landmark::LandmarkDetector;
+use crate::plugins::localization::amcl::AmclFilter;
+use crate::plugins::localization::localization_plugin::Localization;
+use crate::plugins::slam::ekf_slam::EkfSlam;
+use crate::plugins::slam::pose_graph::PoseGraph;
+use crate::plugins::slam::scan_matcher::ScanMatcher;
+use crate::plugins::slam::slam_plugin::Slam;
 
 use bevy::prelude::*;
 
-pub fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.spawn(camera_2d_bundle());
-    commands.spawn(hero_bundle(&asset_server));
+pub fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mode: Res<SimMode>,
+    sensors: Res<HeroSensors>,
+) {
+    if mode.is_windowed() {
+        commands.spawn(camera_2d_bundle());
+    }
+
+    let mut hero = commands.spawn(hero_bundle(&sensors));
+    if sensors.localization == Localization::Amcl {
+        hero.insert(AmclFilter::default());
+    }
+    match sensors.slam {
+        Slam::None => {}
+        Slam::ScanMatching => {
+            hero.insert(ScanMatcher::default());
+        }
+        Slam::PoseGraph => {
+            hero.insert((ScanMatcher::default(), PoseGraph::default()));
+        }
+        Slam::Ekf => {
+            hero.insert((LandmarkDetector::default(), EkfSlam::default()));
+        }
+    }
+    if mode.is_windowed() {
+        hero.insert(hero_sprite_bundle(&asset_server));
+    }
 }
@@ -1,4 +1,10 @@
+use std::fmt::Write;
+
 use crate::components::collectible::CollectionStats;
+use crate::constants::METERS_PER_PIXEL;
+use crate::plugins::sim::coverage::CoverageStats;
+use crate::plugins::sim::sim_metrics::SimMetrics;
+use crate::plugins::sim::sim_time::{SimSpeed, SimTiming};
 use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
 use bevy::prelude::*;
 use bevy::text::{BreakLineOn, JustifyText};
@@ -146,17 +152,15 @@ fn update_top_hud(
     mut q: Query<&mut Text, With<TopHudText>>,
     time: Res<Time>,
     stats: Res<CollectionStats>,
+    coverage: Res<CoverageStats>,
 ) {
-    // TODO: replace these mocks with real data:
-    // - explored_pct derived from your occupancy grid stats (cells visited, or similar)
-
     let collected = stats.collected;
     let total = stats.total;
-    let explored_pct: f32 = 0.0;
+    let explored_pct = coverage.explored_pct;
 
     let sim = time.elapsed();
-    let mins = (sim.as_secs() / 60) as u64;
-    let secs = (sim.as_secs() % 60) as u64;
+    let mins = sim.as_secs() / 60;
+    let secs = sim.as_secs() % 60;
 
     let mut text = q.single_mut();
     text.sections[0].value = format!(
@@ -194,7 +198,7 @@ fn setup_stats_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
                     text: Text {
                         sections: vec![TextSection::new(
                             // perf-only
-                            "Perf/Sim\n  Frame time: --.-ms   FPS: --\n  Sim time: 00:00",
+                            "Perf/Sim\n  Frame time: --.-ms   FPS: --\n  Sim time: 00:00\n  Bumps: 0   Stuck: 0s   Replans: 0",
                             TextStyle {
                                 font,
                                 font_size: 16.0,
@@ -215,6 +219,8 @@ fn update_stats_text(
     diagnostics: Res<bevy::diagnostic::DiagnosticsStore>,
     mut query: Query<&mut Text, With<StatsOverlayText>>,
     time: Res<Time>,
+    timing: Res<SimTiming>,
+    metrics: Res<SimMetrics>,
 ) {
     let fps = diagnostics
         .get(&bevy::diagnostic::FrameTimeDiagnosticsPlugin::FPS)
@@ -230,9 +236,56 @@ fn update_stats_text(
     let minutes = sim_time.as_secs() / 60;
     let seconds = sim_time.as_secs() % 60;
 
+    let speed = match timing.speed {
+        SimSpeed::Paused => "paused".to_string(),
+        SimSpeed::Scaled(scale) => format!("x{scale}"),
+        SimSpeed::Max => "max".to_string(),
+    };
+
     let mut text = query.single_mut();
     text.sections[0].value = format!(
-        "Perf/Sim\n  Frame time: {:.1}ms   FPS: {:.0}\n  Sim time: {:02}:{:02}",
-        frame_time, fps, minutes, seconds,
+        "Perf/Sim\n  Frame time: {:.1}ms   FPS: {:.0}\n  Sim time: {:02}:{:02} ({} @ {:.0}Hz)\n  Bumps: {}   Stuck: {:.0}s   Replans: {}\n  Odom drift: {:.2}m  {:+.1}°",
+        frame_time,
+        fps,
+        minutes,
+        seconds,
+        speed,
+        timing.rate_hz,
+        metrics.wall_contacts,
+        metrics.stuck_secs,
+        metrics.replans,
+        metrics.odometry_error_px * METERS_PER_PIXEL,
+        metrics.odometry_heading_error_deg,
     );
+    if let Some(error) = metrics.localization_error_px {
+        let _ = write!(
+            text.sections[0].value,
+            "\n  Loc error: {:.2}m   Kidnaps: {}",
+            error * METERS_PER_PIXEL,
+            metrics.kidnaps
+        );
+    }
+    if metrics.pose_error_max_px > 0.0 {
+        let _ = write!(
+            text.sections[0].value,
+            "\n  Pose error: {:.2}m   RMSE: {:.2}m",
+            metrics.pose_error_px * METERS_PER_PIXEL,
+            metrics.pose_error_rmse_px() * METERS_PER_PIXEL
+        );
+    }
+    if metrics.loop_closures > 0 {
+        let _ = write!(
+            text.sections[0].value,
+            "\n  Loop closures: {}   Map rebuilds: {}",
+            metrics.loop_closures, metrics.map_rebuilds
+        );
+    }
+    if let Some(error) = metrics.landmark_error_px {
+        let _ = write!(
+            text.sections[0].value,
+            "\n  Landmarks: {}   Landmark error: {:.2}m",
+            metrics.landmarks_mapped,
+            error * METERS_PER_PIXEL
+        );
+    }
 }